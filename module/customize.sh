@@ -40,7 +40,7 @@ KEY_volume_detect() {
   ui_print "================================"
   local timeout=10
   local start_time=$(date +%s)
-  local chosen_mode="Overlay"
+  local chosen_mode="overlay"
   while true; do
     local current_time=$(date +%s)
     if [ $((current_time - start_time)) -ge $timeout ]; then
@@ -49,11 +49,11 @@ KEY_volume_detect() {
     fi
     local key_event=$(timeout 0.5 getevent -l 2>/dev/null)
     if echo "$key_event" | grep -q "KEY_VOLUMEUP"; then
-      chosen_mode="Overlay"
+      chosen_mode="overlay"
       ui_print "Key Detected: Selected OverlayFS"
       break
     elif echo "$key_event" | grep -q "KEY_VOLUMEDOWN"; then
-      chosen_mode="Magic"
+      chosen_mode="magic"
       ui_print "Key Detected: Selected Magic Mount"
       break
     fi
@@ -0,0 +1,10 @@
+MODDIR="${0%/*}"
+BINARY="$MODDIR/Hybrid-Mount"
+
+until [ "$(getprop sys.boot_completed)" = "1" ]; do
+  sleep 1
+done
+
+if [ -f "$BINARY" ]; then
+  "$BINARY" boot-completed 2>&1
+fi
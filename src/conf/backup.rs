@@ -0,0 +1,103 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+
+use crate::{conf::config::Config, utils};
+
+/// Number of previous configs kept as `config.toml.1` (newest) to
+/// `config.toml.N` (oldest).
+pub const CONFIG_BACKUP_COUNT: usize = 5;
+
+#[derive(Debug, Serialize)]
+pub struct ConfigBackup {
+    pub index: usize,
+    pub path: PathBuf,
+    pub modified: u64,
+    pub valid: bool,
+}
+
+pub fn backup_path(path: &Path, index: usize) -> PathBuf {
+    PathBuf::from(format!("{}.{}", path.display(), index))
+}
+
+/// Shifts the existing backups up by one and copies the current file into
+/// slot 1. Nothing happens when the file is missing or `content` would not
+/// change it.
+pub fn rotate(path: &Path, content: &[u8]) -> Result<()> {
+    let current = match fs::read(path) {
+        Ok(current) => current,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e).context("failed to read config for backup"),
+    };
+
+    if current == content {
+        return Ok(());
+    }
+
+    let _ = fs::remove_file(backup_path(path, CONFIG_BACKUP_COUNT));
+    for index in (1..CONFIG_BACKUP_COUNT).rev() {
+        let from = backup_path(path, index);
+        if from.exists() {
+            fs::rename(&from, backup_path(path, index + 1))
+                .with_context(|| format!("failed to rotate {}", from.display()))?;
+        }
+    }
+
+    utils::atomic_write(backup_path(path, 1), current).context("failed to write config backup")
+}
+
+pub fn list(path: &Path) -> Vec<ConfigBackup> {
+    (1..=CONFIG_BACKUP_COUNT)
+        .filter_map(|index| {
+            let backup = backup_path(path, index);
+            let modified = fs::metadata(&backup)
+                .ok()?
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            let valid = Config::read_table(&backup, false)
+                .and_then(Config::from_table)
+                .is_ok();
+
+            Some(ConfigBackup {
+                index,
+                path: backup,
+                modified,
+                valid,
+            })
+        })
+        .collect()
+}
+
+/// Replaces `path` with backup `index`. The replaced file is rotated into
+/// the backups first, so a restore can itself be undone.
+pub fn restore(path: &Path, index: usize) -> Result<()> {
+    if !(1..=CONFIG_BACKUP_COUNT).contains(&index) {
+        bail!("backup index must be between 1 and {}", CONFIG_BACKUP_COUNT);
+    }
+
+    let backup = backup_path(path, index);
+    let content =
+        fs::read(&backup).with_context(|| format!("failed to read {}", backup.display()))?;
+
+    Config::read_table(&backup, false)
+        .and_then(Config::from_table)
+        .with_context(|| format!("refusing to restore invalid backup {}", backup.display()))?;
+
+    rotate(path, &content)?;
+    utils::atomic_write(path, &content).context("failed to restore config")?;
+
+    log::info!("Restored {} from {}", path.display(), backup.display());
+
+    Ok(())
+}
@@ -3,7 +3,7 @@
 
 use std::path::PathBuf;
 
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 
 use crate::defs;
 
@@ -18,32 +18,94 @@ pub struct Cli {
     pub mountsource: Option<String>,
     #[arg(short = 'p', long = "partitions", value_delimiter = ',')]
     pub partitions: Vec<String>,
+    /// JSON file of device properties used instead of the live system when
+    /// evaluating conditional rules
+    #[arg(long = "props")]
+    pub props: Option<PathBuf>,
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
 
+/// JSON input for the commands fed by the WebUI. `--payload` takes it hex
+/// encoded, `--payload-file` and `--stdin` take it as is.
+#[derive(Args, Debug)]
+#[group(multiple = false)]
+pub struct PayloadArgs {
+    #[arg(long)]
+    pub payload: Option<String>,
+    #[arg(long)]
+    pub payload_file: Option<PathBuf>,
+    #[arg(long)]
+    pub stdin: bool,
+}
+
 #[derive(Subcommand, Debug)]
 pub enum Commands {
     GenConfig {
         #[arg(short = 'o', long = "output", default_value = defs::CONFIG_FILE)]
         output: PathBuf,
     },
-    ShowConfig,
+    ShowConfig {
+        #[arg(long)]
+        sources: bool,
+    },
+    #[command(name = "validate-config")]
+    ValidateConfig {
+        #[command(flatten)]
+        input: Option<PayloadArgs>,
+        /// Probe the kernel instead of using the capabilities recorded at boot
+        #[arg(long)]
+        probe: bool,
+    },
     #[command(name = "save-config")]
     SaveConfig {
+        #[command(flatten)]
+        input: PayloadArgs,
         #[arg(long)]
-        payload: String,
+        revision: Option<String>,
+    },
+    #[command(name = "patch-config")]
+    PatchConfig {
+        #[command(flatten)]
+        input: PayloadArgs,
+        #[arg(long)]
+        revision: Option<String>,
+    },
+    #[command(name = "restore-config")]
+    RestoreConfig {
+        /// Backup to restore, 1 being the newest. Lists backups when omitted.
+        index: Option<usize>,
     },
     #[command(name = "save-module-rules")]
     SaveModuleRules {
         #[arg(long)]
         module: String,
-        #[arg(long)]
-        payload: String,
+        #[command(flatten)]
+        input: PayloadArgs,
     },
     Modules,
+    Plan,
+    Explain {
+        path: PathBuf,
+    },
     Conflicts,
     Diagnostics,
+    Doctor {
+        /// Probe the kernel instead of using the capabilities recorded at boot
+        #[arg(long)]
+        probe: bool,
+    },
+    BootCompleted,
+    BisectReset,
+    Teardown,
+    Reload {
+        #[arg(long)]
+        module: String,
+    },
+    Profile {
+        #[command(subcommand)]
+        action: ProfileAction,
+    },
     Poaceae {
         #[arg(short, long, default_value = defs::POACEAE_MOUNT_POINT)]
         target: String,
@@ -53,6 +115,20 @@ pub enum Commands {
     },
 }
 
+#[derive(Subcommand, Debug)]
+pub enum ProfileAction {
+    List,
+    Use {
+        #[arg(required_unless_present = "clear")]
+        name: Option<String>,
+        #[arg(long, conflicts_with = "name")]
+        clear: bool,
+    },
+    Save {
+        name: String,
+    },
+}
+
 #[derive(Subcommand, Debug)]
 pub enum PoaceaeAction {
     Hide {
@@ -1,16 +1,33 @@
-use std::{fs::File, path::Path};
+use std::{
+    fs::{self, File},
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail, ensure};
 use serde::Serialize;
 
 use crate::{
     conf::{
-        cli::{Cli, PoaceaeAction},
+        backup,
+        cli::{Cli, PayloadArgs, PoaceaeAction, ProfileAction},
         config::{self, Config},
+        layered::ConfigSources,
+        patch, validator,
+    },
+    core::{
+        inventory,
+        inventory::model as modules,
+        ops::{planner, reload, teardown},
+        recovery,
+        state::RuntimeState,
     },
-    core::{inventory, inventory::model as modules, ops::planner},
     defs,
-    sys::poaceae,
+    mount::{magic_mount::utils::collect_module_files, node::Node},
+    sys::{
+        kernel::{self, KernelCapabilities},
+        poaceae, props,
+    },
     utils,
 };
 
@@ -21,27 +38,55 @@ struct DiagnosticIssueJson {
     message: String,
 }
 
-fn load_config(cli: &Cli) -> Result<Config> {
-    if let Some(config_path) = &cli.config {
-        return Config::from_file(config_path).with_context(|| {
-            format!(
-                "Failed to load config from custom path: {}",
-                config_path.display()
-            )
+#[derive(Serialize)]
+struct PlanJson<'a> {
+    #[serde(flatten)]
+    plan: &'a planner::MountPlan,
+    magic_tree: Option<Node>,
+}
+
+#[derive(Serialize)]
+struct ShowConfigJson<'a> {
+    #[serde(flatten)]
+    config: &'a Config,
+    revision: String,
+}
+
+#[derive(Serialize)]
+struct ConfigSourcesJson<'a> {
+    config: &'a Config,
+    sources: &'a ConfigSources,
+    revision: String,
+    /// The main file alone, for clients that patch it without drop-ins.
+    file: toml::Table,
+}
+
+/// The file the config commands read and write, and the drop-in directory
+/// layered over it. Drop-ins only apply to the default config file.
+pub fn config_target(cli: &Cli) -> (PathBuf, Option<&'static Path>) {
+    match &cli.config {
+        Some(path) => (path.clone(), None),
+        None => (
+            PathBuf::from(defs::CONFIG_FILE),
+            Some(Path::new(defs::CONFIG_DROPIN_DIR)),
+        ),
+    }
+}
+
+fn load_config_with_sources(cli: &Cli) -> Result<(Config, ConfigSources)> {
+    let (path, dropin_dir) = config_target(cli);
+
+    if cli.config.is_some() {
+        return Config::load_layered(&path, dropin_dir).with_context(|| {
+            format!("Failed to load config from custom path: {}", path.display())
         });
     }
 
-    match Config::load_default() {
-        Ok(config) => Ok(config),
+    match Config::load_layered(&path, dropin_dir) {
+        Ok(loaded) => Ok(loaded),
         Err(e) => {
-            let is_not_found = e
-                .root_cause()
-                .downcast_ref::<std::io::Error>()
-                .map(|io_err| io_err.kind() == std::io::ErrorKind::NotFound)
-                .unwrap_or(false);
-
-            if is_not_found {
-                Ok(Config::default())
+            if config::is_not_found(&e) {
+                Ok((Config::default(), ConfigSources::new()))
             } else {
                 Err(e).context(format!(
                     "Failed to load default config from {}",
@@ -52,58 +97,293 @@ fn load_config(cli: &Cli) -> Result<Config> {
     }
 }
 
+fn load_config(cli: &Cli) -> Result<Config> {
+    let (mut config, _) = load_config_with_sources(cli)?;
+    if let Err(e) = config.apply_active_profile() {
+        log::warn!("Ignoring config profile: {:#}", e);
+    }
+    config.apply_overrides(props::source());
+    Ok(config)
+}
+
+fn read_payload(input: &PayloadArgs) -> Result<Vec<u8>> {
+    if let Some(payload) = &input.payload {
+        return utils::decode_hex(payload).context("Failed to decode hex payload");
+    }
+
+    if let Some(path) = &input.payload_file {
+        return fs::read(path)
+            .with_context(|| format!("Failed to read payload file {}", path.display()));
+    }
+
+    if !input.stdin {
+        bail!("One of --payload, --payload-file or --stdin is required");
+    }
+
+    let mut buf = Vec::new();
+    io::stdin()
+        .read_to_end(&mut buf)
+        .context("Failed to read payload from stdin")?;
+
+    Ok(buf)
+}
+
 pub fn handle_gen_config(output: &Path) -> Result<()> {
     Config::default()
         .save_to_file(output)
         .with_context(|| format!("Failed to save generated config to {}", output.display()))
 }
 
-pub fn handle_show_config(cli: &Cli) -> Result<()> {
-    let config = load_config(cli)?;
+pub fn handle_show_config(cli: &Cli, with_sources: bool) -> Result<()> {
+    let (config, sources) = load_config_with_sources(cli)?;
+    let (path, _) = config_target(cli);
+    let revision = patch::revision(&path)?;
+
+    let json = if with_sources {
+        let file = match Config::read_table(&path, false) {
+            Ok(file) => file,
+            Err(e) if config::is_not_found(&e) => toml::Table::new(),
+            Err(e) => return Err(e),
+        };
+
+        serde_json::to_string(&ConfigSourcesJson {
+            config: &config,
+            sources: &sources,
+            revision,
+            file,
+        })
+    } else {
+        serde_json::to_string(&ShowConfigJson {
+            config: &config,
+            revision,
+        })
+    }
+    .context("Failed to serialize config to JSON")?;
+
+    println!("{}", json);
 
-    let json = serde_json::to_string(&config).context("Failed to serialize config to JSON")?;
+    Ok(())
+}
+
+/// Kernel capabilities for the commands that only inspect: the ones the
+/// daemon recorded at boot, or with `probe` a live probe, which mounts a
+/// scratch tmpfs.
+fn kernel_capabilities(probe: bool) -> Option<KernelCapabilities> {
+    if probe {
+        return Some(kernel::capabilities().clone());
+    }
+
+    RuntimeState::load()
+        .ok()
+        .map(|state| state.capabilities)
+        .filter(|caps| !caps.kernel_release.is_empty())
+}
+
+pub fn handle_validate_config(cli: &Cli, input: Option<&PayloadArgs>, probe: bool) -> Result<()> {
+    let caps = kernel_capabilities(probe);
+    let report = match input {
+        Some(input) => validator::validate_json(&read_payload(input)?, caps.as_ref()),
+        None => {
+            let path = cli
+                .config
+                .clone()
+                .unwrap_or_else(|| PathBuf::from(defs::CONFIG_FILE));
+            let content = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read config file {}", path.display()))?;
+            validator::validate_toml(&content, caps.as_ref())
+        }
+    };
+
+    let json = serde_json::to_string(&report).context("Failed to serialize validation report")?;
 
     println!("{}", json);
 
+    ensure!(report.valid, "Configuration is invalid");
+
     Ok(())
 }
 
-pub fn handle_save_config(payload: &str) -> Result<()> {
-    let json_bytes = (0..payload.len())
-        .step_by(2)
-        .map(|i| u8::from_str_radix(&payload[i..i + 2], 16))
-        .collect::<Result<Vec<u8>, _>>()
-        .context("Failed to decode hex payload")?;
+/// Saves a full config, but only the keys that differ from the layered
+/// config end up in the main file, so drop-in values are never baked in.
+pub fn handle_save_config(cli: &Cli, input: &PayloadArgs, revision: Option<&str>) -> Result<()> {
+    let json_bytes = read_payload(input)?;
 
     let config: Config =
         serde_json::from_slice(&json_bytes).context("Failed to parse config JSON payload")?;
 
-    config
-        .save_to_file(defs::CONFIG_FILE)
-        .context("Failed to save config file")?;
+    let (path, dropin_dir) = config_target(cli);
+
+    let (current, _) = load_config_with_sources(cli)?;
+    let current = serde_json::to_value(&current).context("Failed to serialize current config")?;
+    let file = serde_json::to_value(patch::load_for_update(&path)?)
+        .context("Failed to convert config file")?;
+
+    let mut patch = patch::diff(
+        &current,
+        &serde_json::to_value(&config).context("Failed to serialize config")?,
+    );
+    patch::rebase_lists(&mut patch, &current, &file);
+
+    let outcome = patch::apply(&path, dropin_dir, patch, revision)
+        .with_context(|| format!("Failed to save {}", path.display()))?;
+
+    for key in &outcome.shadowed {
+        log::warn!("'{}' is still overridden by a config.d drop-in", key);
+    }
 
     println!("Configuration saved successfully.");
 
     Ok(())
 }
 
-pub fn handle_save_module_rules(module_id: &str, payload: &str) -> Result<()> {
+pub fn handle_patch_config(cli: &Cli, input: &PayloadArgs, revision: Option<&str>) -> Result<()> {
+    let json_bytes = read_payload(input)?;
+
+    let patch: serde_json::Value =
+        serde_json::from_slice(&json_bytes).context("Failed to parse config patch JSON")?;
+
+    let (path, dropin_dir) = config_target(cli);
+    let outcome = patch::apply(&path, dropin_dir, patch, revision)
+        .with_context(|| format!("Failed to patch {}", path.display()))?;
+
+    for key in &outcome.shadowed {
+        log::warn!("'{}' is still overridden by a config.d drop-in", key);
+    }
+
+    let json = serde_json::to_string(&outcome).context("Failed to serialize patch result")?;
+
+    println!("{}", json);
+
+    Ok(())
+}
+
+pub fn handle_restore_config(cli: &Cli, index: Option<usize>) -> Result<()> {
+    let (path, _) = config_target(cli);
+
+    let Some(index) = index else {
+        let json = serde_json::to_string(&backup::list(&path))
+            .context("Failed to serialize config backups")?;
+        println!("{}", json);
+        return Ok(());
+    };
+
+    backup::restore(&path, index)
+        .with_context(|| format!("Failed to restore {} from backup {}", path.display(), index))?;
+
+    println!("Configuration restored from backup {}.", index);
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct ProfileJson<'a> {
+    name: &'a str,
+    active: bool,
+    #[serde(flatten)]
+    profile: &'a config::ConfigProfile,
+}
+
+pub fn handle_profile(cli: &Cli, action: &ProfileAction) -> Result<()> {
+    let (path, _) = config_target(cli);
+
+    match action {
+        ProfileAction::List => {
+            let (config, _) = load_config_with_sources(cli)?;
+
+            let mut profiles: Vec<ProfileJson> = config
+                .profiles
+                .iter()
+                .map(|(name, profile)| ProfileJson {
+                    name,
+                    active: config.active_profile.as_ref() == Some(name),
+                    profile,
+                })
+                .collect();
+            profiles.sort_by(|a, b| a.name.cmp(b.name));
+
+            let json = serde_json::to_string(&profiles).context("Failed to serialize profiles")?;
+            println!("{}", json);
+        }
+        ProfileAction::Use { name, clear } => {
+            let mut table = patch::load_for_update(&path)?;
+
+            match name {
+                Some(name) if !*clear => {
+                    let (config, _) = load_config_with_sources(cli)?;
+                    if !config.profiles.contains_key(name) {
+                        bail!("Profile '{}' is not defined", name);
+                    }
+                    table.insert(
+                        "active_profile".to_string(),
+                        toml::Value::String(name.clone()),
+                    );
+                    println!("Profile '{}' is now active.", name);
+                }
+                _ => {
+                    table.remove("active_profile");
+                    println!("No profile is active.");
+                }
+            }
+
+            Config::save_table(&path, &table).context("Failed to save active profile")?;
+        }
+        ProfileAction::Save { name } => {
+            config::validate_profile_name(name)?;
+
+            let (config, _) = load_config_with_sources(cli)?;
+            let profile = toml::Value::try_from(config::ConfigProfile::capture(&config))
+                .context("Failed to convert profile")?;
+
+            let mut table = patch::load_for_update(&path)?;
+            let profiles = table
+                .entry("profiles")
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+            let toml::Value::Table(profiles) = profiles else {
+                bail!("'profiles' in {} is not a table", path.display());
+            };
+            profiles.insert(name.clone(), profile);
+
+            Config::save_table(&path, &table).context("Failed to save profile")?;
+
+            println!("Current settings saved as profile '{}'.", name);
+        }
+    }
+
+    Ok(())
+}
+
+pub fn handle_save_module_rules(module_id: &str, input: &PayloadArgs) -> Result<()> {
     utils::validate_module_id(module_id)?;
-    let json_bytes = (0..payload.len())
-        .step_by(2)
-        .map(|i| u8::from_str_radix(&payload[i..i + 2], 16))
-        .collect::<Result<Vec<u8>, _>>()
-        .context("Failed to decode hex payload")?;
+    let json_bytes = read_payload(input)?;
 
     let new_rules: config::ModuleRules =
         serde_json::from_slice(&json_bytes).context("Failed to parse module rules JSON")?;
-    let mut config = Config::load_default().unwrap_or_default();
-
-    config.rules.insert(module_id.to_string(), new_rules);
+    let mut new_rules =
+        toml::Value::try_from(new_rules).context("Failed to convert module rules")?;
+
+    // Only the rule of this module is replaced, everything else in
+    // config.toml is written back as it was.
+    let path = Path::new(defs::CONFIG_FILE);
+    let mut table = patch::load_for_update(path)?;
+
+    let rules = table
+        .entry("rules")
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+    let toml::Value::Table(rules) = rules else {
+        bail!("'rules' in {} is not a table", path.display());
+    };
+
+    // The WebUI does not know about conditional overrides, keep the existing
+    // ones unless the payload brings its own.
+    if let Some(overrides) = rules.get(module_id).and_then(|r| r.get("overrides"))
+        && let toml::Value::Table(new_rules) = &mut new_rules
+        && !new_rules.contains_key("overrides")
+    {
+        new_rules.insert("overrides".to_string(), overrides.clone());
+    }
+    rules.insert(module_id.to_string(), new_rules);
 
-    config
-        .save_to_file(defs::CONFIG_FILE)
-        .context("Failed to update config file with new rules")?;
+    Config::save_table(path, &table).context("Failed to update config file with new rules")?;
 
     println!("Module rules saved for {} into config.toml", module_id);
 
@@ -116,6 +396,63 @@ pub fn handle_modules(cli: &Cli) -> Result<()> {
     modules::print_list(&config).context("Failed to list modules")
 }
 
+pub fn handle_plan(cli: &Cli) -> Result<()> {
+    let config = load_config(cli)?;
+
+    let module_list = inventory::scan(&config.moduledir, &config)
+        .context("Failed to scan modules for mount plan")?;
+
+    let plan = planner::generate(&config, &module_list, &config.moduledir)
+        .context("Failed to generate mount plan")?;
+
+    let magic_tree = if plan.magic_scopes.is_empty() {
+        None
+    } else {
+        collect_module_files(
+            &config.moduledir,
+            &config.magic_partitions(),
+            &plan.magic_scopes,
+            &plan.module_order,
+        )
+        .context("Failed to collect magic mount tree")?
+    };
+
+    let json = serde_json::to_string(&PlanJson {
+        plan: &plan,
+        magic_tree,
+    })
+    .context("Failed to serialize mount plan")?;
+
+    println!("{}", json);
+
+    Ok(())
+}
+
+pub fn handle_explain(cli: &Cli, path: &Path) -> Result<()> {
+    ensure!(
+        path.is_absolute(),
+        "Path must be absolute: {}",
+        path.display()
+    );
+
+    let config = load_config(cli)?;
+
+    let module_list = inventory::scan(&config.moduledir, &config)
+        .context("Failed to scan modules for path explanation")?;
+
+    let plan = planner::generate(&config, &module_list, &config.moduledir)
+        .context("Failed to generate plan for path explanation")?;
+
+    let explanation = plan.explain(path, &module_list, &config.moduledir);
+
+    let json =
+        serde_json::to_string(&explanation).context("Failed to serialize path explanation")?;
+
+    println!("{}", json);
+
+    Ok(())
+}
+
 pub fn handle_conflicts(cli: &Cli) -> Result<()> {
     let config = load_config(cli)?;
 
@@ -125,7 +462,7 @@ pub fn handle_conflicts(cli: &Cli) -> Result<()> {
     let plan = planner::generate(&config, &module_list, &config.moduledir)
         .context("Failed to generate plan for conflict analysis")?;
 
-    let report = plan.analyze();
+    let report = plan.analyze(&config);
 
     let json =
         serde_json::to_string(&report.conflicts).context("Failed to serialize conflict report")?;
@@ -144,11 +481,13 @@ pub fn handle_diagnostics(cli: &Cli) -> Result<()> {
     let plan = planner::generate(&config, &module_list, &config.moduledir)
         .context("Failed to generate plan for diagnostics")?;
 
-    let report = plan.analyze();
+    let report = plan.analyze(&config);
 
     let json_issues: Vec<DiagnosticIssueJson> = report
         .diagnostics
         .into_iter()
+        .chain(inventory::prop_diagnostics(&config.moduledir, &module_list))
+        .chain(recovery::diagnostics())
         .map(|i| DiagnosticIssueJson {
             level: match i.level {
                 planner::DiagnosticLevel::Warning => "Warning".to_string(),
@@ -167,6 +506,56 @@ pub fn handle_diagnostics(cli: &Cli) -> Result<()> {
     Ok(())
 }
 
+pub fn handle_boot_completed() -> Result<()> {
+    recovery::complete_boot()
+}
+
+pub fn handle_bisect_reset() -> Result<()> {
+    recovery::reset_bisection()
+}
+
+pub fn handle_doctor(probe: bool) -> Result<()> {
+    let caps = kernel_capabilities(probe)
+        .context("No kernel capabilities recorded yet, run with --probe to probe them")?;
+    let json = serde_json::to_string(&caps).context("Failed to serialize kernel capabilities")?;
+
+    println!("{}", json);
+
+    Ok(())
+}
+
+pub fn handle_teardown() -> Result<()> {
+    let report = teardown::teardown()?;
+
+    let json = serde_json::to_string(&report).context("Failed to serialize teardown report")?;
+
+    println!("{}", json);
+
+    Ok(())
+}
+
+pub fn handle_reload(cli: &Cli, module: &str) -> Result<()> {
+    let mut config = load_config(cli)?;
+    config.merge_with_cli(
+        cli.moduledir.clone(),
+        cli.mountsource.clone(),
+        cli.partitions.clone(),
+    );
+
+    if utils::check_zygisksu_enforce_status() && !config.allow_umount_coexistence {
+        config.disable_umount = true;
+    }
+
+    let report = reload::reload(&config, module)
+        .with_context(|| format!("Failed to reload module {}", module))?;
+
+    let json = serde_json::to_string(&report).context("Failed to serialize reload report")?;
+
+    println!("{}", json);
+
+    Ok(())
+}
+
 pub fn handle_poaceae(target_path: &str, action: &PoaceaeAction) -> Result<()> {
     let file = File::open(target_path)
         .with_context(|| format!("Failed to open PoaceaeFS root at {}", target_path))?;
@@ -4,10 +4,19 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use serde::{Deserialize, Serialize};
 
-use crate::defs;
+use crate::{
+    conf::{
+        backup,
+        layered::{self, ConfigSources},
+        migration,
+    },
+    defs,
+    sys::props::{self, PropertySource},
+    utils,
+};
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
 #[serde(rename_all = "lowercase")]
@@ -41,19 +50,160 @@ pub struct ModuleRules {
     pub default_mode: MountMode,
     #[serde(default)]
     pub paths: HashMap<String, MountMode>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub overrides: Vec<RuleOverride>,
+}
+
+/// Device conditions, every field that is set has to match. `device` entries
+/// may be globs. Kernel bounds compare only as many components as given, so
+/// `kernel_max = "5.10"` includes every 5.10.x release; a release or bound
+/// without a leading version number never matches.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct RuleCondition {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sdk_min: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sdk_max: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub device: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kernel_min: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kernel_max: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub selinux_enforcing: Option<bool>,
+}
+
+fn version_components(version: &str) -> Vec<u64> {
+    version
+        .split(|c: char| !c.is_ascii_digit() && c != '.')
+        .next()
+        .unwrap_or_default()
+        .split('.')
+        .map_while(|part| part.parse().ok())
+        .collect()
+}
+
+impl RuleCondition {
+    pub fn matches(&self, props: &dyn PropertySource) -> bool {
+        if self.sdk_min.is_some() || self.sdk_max.is_some() {
+            let Some(sdk) = props
+                .get(props::SDK_PROP)
+                .and_then(|v| v.parse::<u32>().ok())
+            else {
+                return false;
+            };
+            if self.sdk_min.is_some_and(|min| sdk < min)
+                || self.sdk_max.is_some_and(|max| sdk > max)
+            {
+                return false;
+            }
+        }
+
+        if !self.device.is_empty() {
+            let device = props.get(props::DEVICE_PROP).unwrap_or_default();
+            if !self
+                .device
+                .iter()
+                .any(|pattern| utils::glob_match_prefix(pattern, &device))
+            {
+                return false;
+            }
+        }
+
+        if self.kernel_min.is_some() || self.kernel_max.is_some() {
+            let Some(kernel) = props.get(props::KERNEL_RELEASE_PROP) else {
+                return false;
+            };
+            let kernel = version_components(&kernel);
+            let within = |bound: &str, ordering: std::cmp::Ordering| {
+                let bound = version_components(bound);
+                let len = bound.len().min(kernel.len());
+                len > 0 && kernel[..len].cmp(&bound[..len]) != ordering
+            };
+            if self
+                .kernel_min
+                .as_deref()
+                .is_some_and(|min| !within(min, std::cmp::Ordering::Less))
+                || self
+                    .kernel_max
+                    .as_deref()
+                    .is_some_and(|max| !within(max, std::cmp::Ordering::Greater))
+            {
+                return false;
+            }
+        }
+
+        if let Some(enforcing) = self.selinux_enforcing {
+            let current = props
+                .get(props::SELINUX_ENFORCE_PROP)
+                .is_some_and(|v| v == "1");
+            if current != enforcing {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct RuleOverride {
+    pub when: RuleCondition,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_mode: Option<MountMode>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub paths: HashMap<String, MountMode>,
+}
+
+/// Settings applied only on devices matching `when`.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ConfigOverride {
+    pub when: RuleCondition,
+    #[serde(flatten)]
+    pub settings: ConfigProfile,
+}
+
+fn rule_specificity(pattern: &str) -> (usize, bool) {
+    (pattern.trim_matches('/').len(), !utils::is_glob(pattern))
 }
 
 impl ModuleRules {
+    /// Path keys are module-relative (`system/app/Foo`) and cover the whole
+    /// subtree they name; they may contain `*`, `?` and `**` globs. The
+    /// longest matching key wins, a plain key beating a glob of equal length.
+    pub fn matching_rule(&self, relative_path: &str) -> Option<(&str, &MountMode)> {
+        self.paths
+            .iter()
+            .filter(|(pattern, _)| utils::glob_match_prefix(pattern, relative_path))
+            .max_by(|(a, _), (b, _)| {
+                rule_specificity(a)
+                    .cmp(&rule_specificity(b))
+                    .then_with(|| b.cmp(a))
+            })
+            .map(|(pattern, mode)| (pattern.as_str(), mode))
+    }
+
     pub fn get_mode(&self, relative_path: &str) -> MountMode {
-        if let Some(mode) = self.paths.get(relative_path) {
-            return mode.clone();
-        }
-        self.default_mode.clone()
+        self.matching_rule(relative_path)
+            .map(|(_, mode)| mode.clone())
+            .unwrap_or_else(|| self.default_mode.clone())
+    }
+
+    pub fn has_rules_below(&self, relative_path: &str) -> bool {
+        let mode = self.get_mode(relative_path);
+
+        self.paths.iter().any(|(pattern, rule_mode)| {
+            *rule_mode != mode && utils::glob_may_match_below(pattern, relative_path)
+        })
     }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Config {
+    #[serde(default = "default_config_version")]
+    pub config_version: i64,
     #[serde(default = "default_moduledir")]
     pub moduledir: PathBuf,
     #[serde(default = "default_mountsource")]
@@ -70,6 +220,100 @@ pub struct Config {
     pub default_mode: DefaultMode,
     #[serde(default)]
     pub rules: HashMap<String, ModuleRules>,
+    #[serde(default)]
+    pub partition_rules: HashMap<String, MountMode>,
+    /// Module id to layer priority. Higher wins, unlisted modules are 0.
+    #[serde(default)]
+    pub priority: HashMap<String, i32>,
+    /// Module-relative path pattern to the module that wins conflicts below it.
+    #[serde(default)]
+    pub conflict_resolution: HashMap<String, String>,
+    #[serde(default)]
+    pub sync: SyncConfig,
+    #[serde(default)]
+    pub active_profile: Option<String>,
+    #[serde(default)]
+    pub profiles: HashMap<String, ConfigProfile>,
+    #[serde(default)]
+    pub overrides: Vec<ConfigOverride>,
+}
+
+/// How modules are copied into storage.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct SyncConfig {
+    /// Also compare file contents when deciding whether a module changed,
+    /// not only size, mtime, mode and xattrs.
+    #[serde(default)]
+    pub hash: bool,
+    /// Module-relative patterns copied in addition to the partition
+    /// directories and `module.prop`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    /// Module-relative patterns never copied.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+}
+
+/// Named partial override of the top-level settings. Unset fields keep the
+/// value of the base config, `rules` replace the base rule of each module
+/// they name.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ConfigProfile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub moduledir: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mountsource: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub partitions: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub overlay_mode: Option<OverlayMode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disable_umount: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allow_umount_coexistence: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_mode: Option<DefaultMode>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub partition_rules: HashMap<String, MountMode>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub priority: HashMap<String, i32>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub rules: HashMap<String, ModuleRules>,
+}
+
+impl ConfigProfile {
+    /// Captures the current settings. `mountsource` is left out since it is
+    /// detected on every boot.
+    pub fn capture(config: &Config) -> Self {
+        Self {
+            moduledir: Some(config.moduledir.clone()),
+            mountsource: None,
+            partitions: Some(config.partitions.clone()),
+            overlay_mode: Some(config.overlay_mode.clone()),
+            disable_umount: Some(config.disable_umount),
+            allow_umount_coexistence: Some(config.allow_umount_coexistence),
+            default_mode: Some(config.default_mode.clone()),
+            partition_rules: config.partition_rules.clone(),
+            priority: config.priority.clone(),
+            rules: config.rules.clone(),
+        }
+    }
+}
+
+pub fn validate_profile_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        bail!("Invalid profile name: '{name}'. Use letters, digits, '_' and '-'");
+    }
+    Ok(())
+}
+
+fn default_config_version() -> i64 {
+    migration::CONFIG_VERSION
 }
 
 fn default_moduledir() -> PathBuf {
@@ -104,6 +348,7 @@ where
 impl Default for Config {
     fn default() -> Self {
         Self {
+            config_version: default_config_version(),
             moduledir: default_moduledir(),
             mountsource: default_mountsource(),
             partitions: Vec::new(),
@@ -112,35 +357,231 @@ impl Default for Config {
             allow_umount_coexistence: false,
             default_mode: DefaultMode::default(),
             rules: HashMap::new(),
+            partition_rules: HashMap::new(),
+            priority: HashMap::new(),
+            conflict_resolution: HashMap::new(),
+            sync: SyncConfig::default(),
+            active_profile: None,
+            profiles: HashMap::new(),
+            overrides: Vec::new(),
         }
     }
 }
 
+pub fn is_not_found(e: &anyhow::Error) -> bool {
+    e.root_cause()
+        .downcast_ref::<std::io::Error>()
+        .is_some_and(|io| io.kind() == std::io::ErrorKind::NotFound)
+}
+
 impl Config {
+    pub fn read_table(path: &Path, persist_migration: bool) -> Result<toml::Table> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("failed to read config file {}", path.display()))?;
+
+        let mut table: toml::Table = toml::from_str(&content)
+            .with_context(|| format!("failed to parse config file {}", path.display()))?;
+
+        if let Some(version) = migration::migrate(&mut table)
+            .with_context(|| format!("failed to migrate config file {}", path.display()))?
+            && persist_migration
+            && let Err(e) = migration::write_back(path, &content, &table, version)
+        {
+            log::warn!("Failed to persist migrated config: {:#}", e);
+        }
+
+        Ok(table)
+    }
+
+    pub fn from_table(table: toml::Table) -> Result<Self> {
+        toml::Value::Table(table)
+            .try_into()
+            .context("failed to parse config file")
+    }
+
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let content = fs::read_to_string(path.as_ref()).context("failed to read config file")?;
+        Self::from_table(Self::read_table(path.as_ref(), false)?)
+    }
+
+    /// Rewrites an outdated config at `path` in the current format. Reads
+    /// only migrate in memory, this is left to the boot daemon and the
+    /// commands that write the config anyway.
+    pub fn migrate_file(path: &Path) -> Result<()> {
+        match Self::read_table(path, true) {
+            Err(e) if !is_not_found(&e) => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Layers every `*.toml` in `dropin_dir` over `main` in lexical order.
+    /// Drop-ins are migrated in memory only, they belong to whoever
+    /// installed them.
+    pub fn with_dropins(
+        path: &Path,
+        main: toml::Table,
+        dropin_dir: Option<&Path>,
+    ) -> Result<(Self, ConfigSources)> {
+        let mut table = toml::Table::new();
+        let mut sources = ConfigSources::new();
+
+        layered::merge(&mut table, main, path, &mut sources);
+
+        for dropin in dropin_dir.map(layered::dropin_files).unwrap_or_default() {
+            let layer = Self::read_table(&dropin, false)?;
+            layered::merge(&mut table, layer, &dropin, &mut sources);
+        }
 
-        let config: Config = toml::from_str(&content).context("failed to parse config file")?;
+        Ok((Self::from_table(table)?, sources))
+    }
+
+    pub fn load_layered(path: &Path, dropin_dir: Option<&Path>) -> Result<(Self, ConfigSources)> {
+        let main = match Self::read_table(path, false) {
+            Ok(main) => main,
+            Err(e)
+                if is_not_found(&e)
+                    && dropin_dir.is_some_and(|dir| !layered::dropin_files(dir).is_empty()) =>
+            {
+                toml::Table::new()
+            }
+            Err(e) => return Err(e),
+        };
 
-        Ok(config)
+        Self::with_dropins(path, main, dropin_dir)
     }
 
     pub fn load_default() -> Result<Self> {
-        Self::from_file(defs::CONFIG_FILE)
+        Self::load_layered(
+            Path::new(defs::CONFIG_FILE),
+            Some(Path::new(defs::CONFIG_DROPIN_DIR)),
+        )
+        .map(|(config, _)| config)
+    }
+
+    fn write_file(path: &Path, content: String) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).context("failed to create config directory")?;
+        }
+
+        if let Err(e) = backup::rotate(path, content.as_bytes()) {
+            log::warn!("Failed to back up {}: {:#}", path.display(), e);
+        }
+
+        utils::atomic_write(path, content).context("failed to write config file")?;
+
+        Ok(())
     }
 
     pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
         let content = toml::to_string_pretty(self).context("failed to serialize config")?;
 
-        if let Some(parent) = path.as_ref().parent() {
-            fs::create_dir_all(parent).context("failed to create config directory")?;
+        Self::write_file(path.as_ref(), content)
+    }
+
+    /// Writes a raw table, keeping keys the caller did not touch exactly as
+    /// the user left them.
+    pub fn save_table(path: &Path, table: &toml::Table) -> Result<()> {
+        let content = toml::to_string_pretty(table).context("failed to serialize config")?;
+
+        Self::write_file(path, content)
+    }
+
+    fn apply_settings(&mut self, settings: ConfigProfile) {
+        if let Some(moduledir) = settings.moduledir {
+            self.moduledir = moduledir;
+        }
+        if let Some(mountsource) = settings.mountsource {
+            self.mountsource = mountsource;
+        }
+        if let Some(partitions) = settings.partitions {
+            self.partitions = partitions;
+        }
+        if let Some(overlay_mode) = settings.overlay_mode {
+            self.overlay_mode = overlay_mode;
         }
+        if let Some(disable_umount) = settings.disable_umount {
+            self.disable_umount = disable_umount;
+        }
+        if let Some(allow) = settings.allow_umount_coexistence {
+            self.allow_umount_coexistence = allow;
+        }
+        if let Some(default_mode) = settings.default_mode {
+            self.default_mode = default_mode;
+        }
+        self.partition_rules.extend(settings.partition_rules);
+        self.priority.extend(settings.priority);
+        self.rules.extend(settings.rules);
+    }
+
+    pub fn apply_active_profile(&mut self) -> Result<()> {
+        let Some(name) = &self.active_profile else {
+            return Ok(());
+        };
+        let Some(profile) = self.profiles.get(name).cloned() else {
+            bail!("active profile '{}' is not defined", name);
+        };
 
-        fs::write(path.as_ref(), content).context("failed to write config file")?;
+        let name = name.clone();
+        self.apply_settings(profile);
+
+        log::info!("Applied config profile '{}'", name);
 
         Ok(())
     }
 
+    /// Applies the `overrides` whose conditions hold on this device, in
+    /// order, so a later match wins.
+    pub fn apply_overrides(&mut self, props: &dyn PropertySource) {
+        for (i, entry) in std::mem::take(&mut self.overrides).into_iter().enumerate() {
+            if entry.when.matches(props) {
+                log::debug!("Applying config override {}", i);
+                self.apply_settings(entry.settings);
+            }
+        }
+    }
+
+    /// `partition_rules` as module path patterns. A partition other than
+    /// `system` is also matched below `system/`, where modules usually ship it.
+    pub fn partition_rule_patterns(&self) -> Vec<(String, MountMode)> {
+        self.partition_rules
+            .iter()
+            .flat_map(|(partition, mode)| {
+                let partition = partition.trim_matches('/');
+                let nested = (partition != "system").then(|| format!("system/{}", partition));
+                std::iter::once(partition.to_string())
+                    .chain(nested)
+                    .map(move |pattern| (pattern, mode.clone()))
+            })
+            .collect()
+    }
+
+    /// Partitions magic mount accepts besides `system`: the configured ones
+    /// plus those a `partition_rules` entry switches to magic, since their
+    /// top-level module directories become magic scopes.
+    pub fn magic_partitions(&self) -> Vec<String> {
+        let mut partitions = self.partitions.clone();
+        for (partition, mode) in &self.partition_rules {
+            let partition = partition.trim_matches('/');
+            if *mode == MountMode::Magic && !partitions.iter().any(|p| p == partition) {
+                partitions.push(partition.to_string());
+            }
+        }
+        partitions
+    }
+
+    /// Most specific `conflict_resolution` entry covering `relative_path`, as
+    /// `(pattern, module id)`.
+    pub fn conflict_winner(&self, relative_path: &str) -> Option<(&str, &str)> {
+        self.conflict_resolution
+            .iter()
+            .filter(|(pattern, _)| utils::glob_match_prefix(pattern, relative_path))
+            .max_by(|(a, _), (b, _)| {
+                rule_specificity(a)
+                    .cmp(&rule_specificity(b))
+                    .then_with(|| b.cmp(a))
+            })
+            .map(|(pattern, id)| (pattern.as_str(), id.as_str()))
+    }
+
     pub fn merge_with_cli(
         &mut self,
         moduledir: Option<PathBuf>,
@@ -160,3 +601,300 @@ impl Config {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sys::props::StaticProperties;
+
+    fn props(entries: &[(&str, &str)]) -> StaticProperties {
+        StaticProperties(
+            entries
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        )
+    }
+
+    fn pixel() -> StaticProperties {
+        props(&[
+            (props::SDK_PROP, "34"),
+            (props::DEVICE_PROP, "husky"),
+            (props::KERNEL_RELEASE_PROP, "5.10.198-android12-9-g1c2b3a4"),
+            (props::SELINUX_ENFORCE_PROP, "1"),
+        ])
+    }
+
+    #[test]
+    fn empty_condition_always_matches() {
+        assert!(RuleCondition::default().matches(&pixel()));
+        assert!(RuleCondition::default().matches(&props(&[])));
+    }
+
+    #[test]
+    fn sdk_bounds_are_inclusive() {
+        let within = |sdk_min, sdk_max| {
+            RuleCondition {
+                sdk_min,
+                sdk_max,
+                ..Default::default()
+            }
+            .matches(&pixel())
+        };
+
+        assert!(within(Some(34), None));
+        assert!(within(None, Some(34)));
+        assert!(within(Some(30), Some(35)));
+        assert!(!within(Some(35), None));
+        assert!(!within(None, Some(33)));
+    }
+
+    #[test]
+    fn device_matches_names_and_globs() {
+        let device = |names: &[&str]| {
+            RuleCondition {
+                device: names.iter().map(|n| n.to_string()).collect(),
+                ..Default::default()
+            }
+            .matches(&pixel())
+        };
+
+        assert!(device(&["husky"]));
+        assert!(device(&["shiba", "husky"]));
+        assert!(device(&["hus*"]));
+        assert!(device(&["h?sky"]));
+        assert!(!device(&["husk"]));
+        assert!(!device(&["shiba", "sh*"]));
+    }
+
+    #[test]
+    fn selinux_state() {
+        let enforcing = |value: bool, props: &StaticProperties| {
+            RuleCondition {
+                selinux_enforcing: Some(value),
+                ..Default::default()
+            }
+            .matches(props)
+        };
+        let permissive = props(&[(props::SELINUX_ENFORCE_PROP, "0")]);
+
+        assert!(enforcing(true, &pixel()));
+        assert!(!enforcing(false, &pixel()));
+        assert!(enforcing(false, &permissive));
+        assert!(!enforcing(true, &permissive));
+    }
+
+    #[test]
+    fn kernel_bounds_compare_given_components() {
+        let kernel = |release: &str, min: Option<&str>, max: Option<&str>| {
+            RuleCondition {
+                kernel_min: min.map(str::to_string),
+                kernel_max: max.map(str::to_string),
+                ..Default::default()
+            }
+            .matches(&props(&[(props::KERNEL_RELEASE_PROP, release)]))
+        };
+
+        assert_eq!(version_components("5.10.198-android12"), [5, 10, 198]);
+        assert_eq!(version_components("6.1.57+"), [6, 1, 57]);
+        assert_eq!(version_components("4.19"), [4, 19]);
+
+        assert!(kernel("5.10.198-android12", Some("5.10"), None));
+        assert!(kernel("5.10.198-android12", None, Some("5.10")));
+        assert!(kernel("5.10.198-android12", Some("5.4"), Some("5.15")));
+        assert!(kernel(
+            "5.10.198-android12",
+            Some("5.10.198"),
+            Some("5.10.198")
+        ));
+        assert!(!kernel("5.10.198-android12", Some("5.10.199"), None));
+        assert!(!kernel("5.10.198-android12", Some("5.15"), None));
+        assert!(!kernel("5.10.198-android12", None, Some("5.4")));
+        assert!(kernel("6.1.57-android14-11", Some("6"), None));
+        assert!(!kernel("4.19.157-perf+", Some("5"), None));
+
+        assert_eq!(version_components("unknown"), Vec::<u64>::new());
+        assert!(!kernel("unknown", Some("5.10"), None));
+        assert!(!kernel("unknown", None, Some("5.10")));
+        assert!(!kernel("5.10.198-android12", Some("latest"), None));
+        assert!(!kernel("5.10.198-android12", None, Some("")));
+    }
+
+    #[test]
+    fn missing_props_never_satisfy_a_bound() {
+        let empty = props(&[]);
+
+        let sdk = RuleCondition {
+            sdk_min: Some(1),
+            ..Default::default()
+        };
+        let device = RuleCondition {
+            device: vec!["*".to_string()],
+            ..Default::default()
+        };
+        let kernel = RuleCondition {
+            kernel_max: Some("99".to_string()),
+            ..Default::default()
+        };
+        let sdk_garbage = props(&[(props::SDK_PROP, "UpsideDownCake")]);
+
+        assert!(!sdk.matches(&empty));
+        assert!(!sdk.matches(&sdk_garbage));
+        assert!(!device.matches(&empty));
+        assert!(!kernel.matches(&empty));
+
+        // Without selinuxfs the system is treated as not enforcing.
+        let selinux = RuleCondition {
+            selinux_enforcing: Some(false),
+            ..Default::default()
+        };
+        assert!(selinux.matches(&empty));
+    }
+
+    fn write_layers(dir: &Path, layers: &[(&str, &str)]) -> (PathBuf, PathBuf) {
+        let main = dir.join("config.toml");
+        let dropins = dir.join("config.d");
+        fs::create_dir_all(&dropins).unwrap();
+        for (name, content) in layers {
+            let path = match *name {
+                "config.toml" => main.clone(),
+                _ => dropins.join(name),
+            };
+            fs::write(path, content).unwrap();
+        }
+        (main, dropins)
+    }
+
+    #[test]
+    fn dropins_apply_in_lexical_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let (main, dropins) = write_layers(
+            dir.path(),
+            &[
+                (
+                    "config.toml",
+                    "moduledir = \"/main\"\npartitions = [\"my_a\"]\n",
+                ),
+                ("20-b.toml", "moduledir = \"/b\"\n"),
+                (
+                    "10-a.toml",
+                    "moduledir = \"/a\"\ndisable_umount = true\npartitions = [\"my_a\", \"my_b\"]\n",
+                ),
+                ("30-c.toml.bak", "moduledir = \"/c\"\n"),
+            ],
+        );
+        let source = |name: &str| dropins.join(name).display().to_string();
+
+        let (config, sources) = Config::load_layered(&main, Some(&dropins)).unwrap();
+
+        assert_eq!(config.moduledir, PathBuf::from("/b"));
+        assert!(config.disable_umount);
+        assert_eq!(config.partitions, ["my_a", "my_b"]);
+
+        assert_eq!(sources["moduledir"], source("20-b.toml"));
+        assert_eq!(sources["disable_umount"], source("10-a.toml"));
+        assert_eq!(sources["partitions[0]"], main.display().to_string());
+        assert_eq!(sources["partitions[1]"], source("10-a.toml"));
+        assert!(!sources.contains_key("partitions[2]"));
+    }
+
+    #[test]
+    fn tables_merge_per_key_and_scalars_are_replaced() {
+        let dir = tempfile::tempdir().unwrap();
+        let (main, dropins) = write_layers(
+            dir.path(),
+            &[
+                (
+                    "config.toml",
+                    r#"
+disable_umount = true
+
+[priority]
+foo = 1
+bar = 2
+
+[rules.foo]
+default_mode = "magic"
+
+[rules.foo.paths]
+"system/app" = "ignore"
+
+[[overrides]]
+when = { sdk_min = 30 }
+disable_umount = false
+"#,
+                ),
+                (
+                    "10-a.toml",
+                    r#"
+disable_umount = false
+
+[priority]
+foo = 5
+
+[rules.foo.paths]
+"system/app" = "overlay"
+"system/bin" = "magic"
+
+[rules.bar]
+default_mode = "ignore"
+
+[[overrides]]
+when = { sdk_max = 29 }
+disable_umount = true
+"#,
+                ),
+            ],
+        );
+        let main_source = main.display().to_string();
+        let dropin_source = dropins.join("10-a.toml").display().to_string();
+
+        let (config, sources) = Config::load_layered(&main, Some(&dropins)).unwrap();
+
+        assert!(!config.disable_umount);
+        assert_eq!(config.priority["foo"], 5);
+        assert_eq!(config.priority["bar"], 2);
+
+        let foo = &config.rules["foo"];
+        assert_eq!(foo.default_mode, MountMode::Magic);
+        assert_eq!(foo.paths["system/app"], MountMode::Overlay);
+        assert_eq!(foo.paths["system/bin"], MountMode::Magic);
+        assert_eq!(config.rules["bar"].default_mode, MountMode::Ignore);
+
+        assert_eq!(config.overrides.len(), 2);
+        assert_eq!(config.overrides[0].when.sdk_min, Some(30));
+        assert_eq!(config.overrides[1].when.sdk_max, Some(29));
+
+        assert_eq!(sources["disable_umount"], dropin_source);
+        assert_eq!(sources["priority.foo"], dropin_source);
+        assert_eq!(sources["priority.bar"], main_source);
+        assert_eq!(sources["rules.foo.default_mode"], main_source);
+        assert_eq!(sources["rules.foo.paths.system/app"], dropin_source);
+        assert_eq!(sources["rules.foo.paths.system/bin"], dropin_source);
+        assert_eq!(sources["rules.bar.default_mode"], dropin_source);
+        assert_eq!(sources["overrides[0]"], main_source);
+        assert_eq!(sources["overrides[1]"], dropin_source);
+    }
+
+    #[test]
+    fn later_layers_replace_values_inside_keyed_tables() {
+        let mut table = toml::Table::new();
+        let mut sources = ConfigSources::new();
+
+        layered::merge(
+            &mut table,
+            toml::from_str("[sync]\nhash = true\n").unwrap(),
+            Path::new("main.toml"),
+            &mut sources,
+        );
+        layered::merge(
+            &mut table,
+            toml::from_str("[sync]\nhash = false\n").unwrap(),
+            Path::new("10-a.toml"),
+            &mut sources,
+        );
+
+        assert_eq!(table["sync"]["hash"].as_bool(), Some(false));
+        assert_eq!(sources["sync.hash"], "10-a.toml");
+    }
+}
@@ -0,0 +1,158 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use toml::{Table, Value};
+
+/// Effective key path (`moduledir`, `partitions[1]`, `rules.foo.paths.system`)
+/// to the file that set it.
+pub type ConfigSources = BTreeMap<String, String>;
+
+pub fn dropin_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut files: Vec<PathBuf> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+    files.sort();
+    files
+}
+
+fn merge_rule(
+    base: &mut Table,
+    layer: Table,
+    prefix: &str,
+    source: &str,
+    sources: &mut ConfigSources,
+) {
+    for (key, value) in layer {
+        match (key.as_str(), value) {
+            ("paths", Value::Table(paths)) => {
+                let base_paths = base
+                    .entry("paths")
+                    .or_insert_with(|| Value::Table(Table::new()));
+                if !base_paths.is_table() {
+                    *base_paths = Value::Table(Table::new());
+                }
+                if let Value::Table(base_paths) = base_paths {
+                    for (pattern, mode) in paths {
+                        sources.insert(format!("{}.paths.{}", prefix, pattern), source.to_string());
+                        base_paths.insert(pattern, mode);
+                    }
+                }
+            }
+            (_, value) => {
+                sources.insert(format!("{}.{}", prefix, key), source.to_string());
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Layers `layer` on top of `base`. Scalars are replaced, `partitions` is a
+/// union that keeps the first occurrence, `rules` merge per module with
+/// `paths` merged per pattern, `profiles`, `partition_rules`, `priority`,
+/// `conflict_resolution` and `sync` are replaced per key and `overrides` are
+/// appended.
+pub fn merge(base: &mut Table, layer: Table, source: &Path, sources: &mut ConfigSources) {
+    let source = source.display().to_string();
+
+    for (key, value) in layer {
+        match (key.as_str(), value) {
+            ("partitions", Value::Array(items)) => {
+                let base_items = base
+                    .entry("partitions")
+                    .or_insert_with(|| Value::Array(Vec::new()));
+                if !base_items.is_array() {
+                    *base_items = Value::Array(Vec::new());
+                }
+                if let Value::Array(base_items) = base_items {
+                    for item in items {
+                        if !base_items.contains(&item) {
+                            sources.insert(
+                                format!("partitions[{}]", base_items.len()),
+                                source.clone(),
+                            );
+                            base_items.push(item);
+                        }
+                    }
+                }
+            }
+            ("rules", Value::Table(rules)) => {
+                let base_rules = base
+                    .entry("rules")
+                    .or_insert_with(|| Value::Table(Table::new()));
+                if !base_rules.is_table() {
+                    *base_rules = Value::Table(Table::new());
+                }
+                if let Value::Table(base_rules) = base_rules {
+                    for (id, rule) in rules {
+                        let prefix = format!("rules.{}", id);
+                        match rule {
+                            Value::Table(rule) => {
+                                let base_rule = base_rules
+                                    .entry(id)
+                                    .or_insert_with(|| Value::Table(Table::new()));
+                                if !base_rule.is_table() {
+                                    *base_rule = Value::Table(Table::new());
+                                }
+                                if let Value::Table(base_rule) = base_rule {
+                                    merge_rule(base_rule, rule, &prefix, &source, sources);
+                                }
+                            }
+                            other => {
+                                sources.insert(prefix, source.clone());
+                                base_rules.insert(id, other);
+                            }
+                        }
+                    }
+                }
+            }
+            ("overrides", Value::Array(entries)) => {
+                let base_entries = base
+                    .entry("overrides")
+                    .or_insert_with(|| Value::Array(Vec::new()));
+                if !base_entries.is_array() {
+                    *base_entries = Value::Array(Vec::new());
+                }
+                if let Value::Array(base_entries) = base_entries {
+                    for entry in entries {
+                        sources
+                            .insert(format!("overrides[{}]", base_entries.len()), source.clone());
+                        base_entries.push(entry);
+                    }
+                }
+            }
+            (
+                "profiles" | "partition_rules" | "priority" | "conflict_resolution" | "sync",
+                Value::Table(entries),
+            ) => {
+                let base_entries = base
+                    .entry(key.as_str())
+                    .or_insert_with(|| Value::Table(Table::new()));
+                if !base_entries.is_table() {
+                    *base_entries = Value::Table(Table::new());
+                }
+                if let Value::Table(base_entries) = base_entries {
+                    for (name, entry) in entries {
+                        sources.insert(format!("{}.{}", key, name), source.clone());
+                        base_entries.insert(name, entry);
+                    }
+                }
+            }
+            (_, value) => {
+                sources.insert(key.clone(), source.clone());
+                base.insert(key, value);
+            }
+        }
+    }
+}
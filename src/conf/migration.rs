@@ -0,0 +1,183 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use toml::{Table, Value};
+
+use crate::utils;
+
+pub const CONFIG_VERSION: i64 = 1;
+
+// MIGRATIONS[n] upgrades a table from version n to n + 1.
+const MIGRATIONS: &[fn(&mut Table)] = &[migrate_v0_to_v1];
+
+fn lowercase_value(value: &mut Value) {
+    if let Some(s) = value.as_str() {
+        *value = Value::String(s.to_lowercase());
+    }
+}
+
+// v0 configs were written by older installers and WebUI builds: modes were
+// capitalized (`Overlay`, `TMPFS`), `partitions` could be a comma separated
+// string, and `logfile`/`backup` were still accepted.
+fn migrate_v0_to_v1(table: &mut Table) {
+    table.remove("logfile");
+    table.remove("backup");
+
+    if let Some(Value::String(partitions)) = table.get("partitions") {
+        let list = partitions
+            .split(',')
+            .map(|item| item.trim())
+            .filter(|item| !item.is_empty())
+            .map(|item| Value::String(item.to_string()))
+            .collect();
+        table.insert("partitions".to_string(), Value::Array(list));
+    }
+
+    for key in ["default_mode", "overlay_mode"] {
+        if let Some(value) = table.get_mut(key) {
+            lowercase_value(value);
+        }
+    }
+
+    if let Some(Value::Table(rules)) = table.get_mut("rules") {
+        for (_, rule) in rules.iter_mut() {
+            let Value::Table(rule) = rule else {
+                continue;
+            };
+            if let Some(mode) = rule.get_mut("default_mode") {
+                lowercase_value(mode);
+            }
+            if let Some(Value::Table(paths)) = rule.get_mut("paths") {
+                paths.iter_mut().for_each(|(_, mode)| lowercase_value(mode));
+            }
+        }
+    }
+}
+
+/// Upgrades `table` in place to `CONFIG_VERSION`, returning the version it
+/// started from when anything had to change.
+pub fn migrate(table: &mut Table) -> Result<Option<i64>> {
+    let version = match table.get("config_version") {
+        None => 0,
+        Some(value) => value
+            .as_integer()
+            .context("config_version must be an integer")?,
+    };
+
+    if version > CONFIG_VERSION {
+        bail!(
+            "config_version {} is newer than the supported version {}",
+            version,
+            CONFIG_VERSION
+        );
+    }
+    if version == CONFIG_VERSION {
+        return Ok(None);
+    }
+
+    for (from, step) in MIGRATIONS.iter().enumerate().skip(version.max(0) as usize) {
+        step(table);
+        log::info!("Migrated config from version {} to {}", from, from + 1);
+    }
+    table.insert("config_version".to_string(), Value::Integer(CONFIG_VERSION));
+
+    Ok(Some(version))
+}
+
+pub fn write_back(path: &Path, original: &str, table: &Table, from_version: i64) -> Result<()> {
+    let backup = format!("{}.v{}.bak", path.display(), from_version);
+
+    utils::atomic_write(&backup, original)
+        .with_context(|| format!("failed to write backup {}", backup))?;
+
+    let content = toml::to_string_pretty(table).context("failed to serialize migrated config")?;
+    utils::atomic_write(path, content).context("failed to write migrated config")?;
+
+    log::info!("Original config saved to {}", backup);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::conf::config::{Config, DefaultMode, MountMode, OverlayMode};
+
+    const V0: &str = r#"
+moduledir = "/data/adb/modules"
+logfile = "/data/adb/hybrid-mount/daemon.log"
+backup = true
+partitions = "my_product, odm,,"
+default_mode = "Magic"
+overlay_mode = "TMPFS"
+
+[rules.foo]
+default_mode = "Overlay"
+
+[rules.foo.paths]
+"system/app" = "Ignore"
+"#;
+
+    #[test]
+    fn v0_is_upgraded_in_place() {
+        let mut table: Table = toml::from_str(V0).unwrap();
+
+        assert_eq!(migrate(&mut table).unwrap(), Some(0));
+        assert_eq!(table["config_version"].as_integer(), Some(CONFIG_VERSION));
+        assert!(!table.contains_key("logfile"));
+        assert!(!table.contains_key("backup"));
+        assert_eq!(
+            table["partitions"],
+            Value::Array(vec!["my_product".into(), "odm".into()])
+        );
+        assert_eq!(table["default_mode"].as_str(), Some("magic"));
+        assert_eq!(table["overlay_mode"].as_str(), Some("tmpfs"));
+
+        // Already current, nothing left to do.
+        assert_eq!(migrate(&mut table).unwrap(), None);
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        let mut table: Table = toml::from_str("config_version = 99").unwrap();
+        assert!(migrate(&mut table).is_err());
+
+        let mut table: Table = toml::from_str("config_version = \"1\"").unwrap();
+        assert!(migrate(&mut table).is_err());
+    }
+
+    #[test]
+    fn migrated_file_round_trips_as_v1() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, V0).unwrap();
+
+        Config::migrate_file(&path).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(dir.path().join("config.toml.v0.bak")).unwrap(),
+            V0
+        );
+
+        let content = fs::read_to_string(&path).unwrap();
+        let mut table: Table = toml::from_str(&content).unwrap();
+        assert_eq!(migrate(&mut table).unwrap(), None);
+
+        let config = Config::from_table(table).unwrap();
+        assert_eq!(config.partitions, ["my_product", "odm"]);
+        assert_eq!(config.default_mode, DefaultMode::Magic);
+        assert!(matches!(config.overlay_mode, OverlayMode::Tmpfs));
+        let rules = &config.rules["foo"];
+        assert_eq!(rules.default_mode, MountMode::Overlay);
+        assert_eq!(rules.paths["system/app"], MountMode::Ignore);
+
+        // A current file is left alone.
+        Config::migrate_file(&path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), content);
+    }
+}
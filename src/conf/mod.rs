@@ -1,6 +1,11 @@
 // Copyright 2025 Hybrid Mount Authors
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+pub mod backup;
 pub mod cli;
 pub mod cli_handlers;
 pub mod config;
+pub mod layered;
+pub mod migration;
+pub mod patch;
+pub mod validator;
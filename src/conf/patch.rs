@@ -0,0 +1,240 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::{
+    conf::config::{self, Config},
+    utils,
+};
+
+#[derive(Debug, Serialize)]
+pub struct PatchOutcome {
+    pub revision: String,
+    /// Patched keys that a drop-in still overrides in the effective config.
+    pub shadowed: Vec<String>,
+}
+
+/// Hash of the config file as stored on disk. A missing file hashes like an
+/// empty one.
+pub fn revision(path: &Path) -> Result<String> {
+    let content = match fs::read(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => {
+            return Err(e)
+                .with_context(|| format!("failed to read config file {}", path.display()));
+        }
+    };
+
+    Ok(utils::sha256_hex(&content))
+}
+
+pub fn check_revision(path: &Path, expected: Option<&str>) -> Result<()> {
+    let Some(expected) = expected else {
+        return Ok(());
+    };
+
+    let current = revision(path)?;
+    if current != expected {
+        bail!(
+            "{} changed since revision {} (now {}), reload it and retry",
+            path.display(),
+            expected,
+            current
+        );
+    }
+
+    Ok(())
+}
+
+/// Reads the table at `path` for a read-modify-write. A missing file starts
+/// empty, an unparseable one is an error so it never gets replaced by
+/// defaults.
+pub fn load_for_update(path: &Path) -> Result<toml::Table> {
+    match Config::read_table(path, true) {
+        Ok(table) => Ok(table),
+        Err(e) if config::is_not_found(&e) => Ok(toml::Table::new()),
+        Err(e) => Err(e).context(format!(
+            "refusing to update {}, fix or restore it first",
+            path.display()
+        )),
+    }
+}
+
+/// RFC 7386 JSON merge patch.
+pub fn merge_patch(target: &mut Value, patch: Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch;
+        return;
+    };
+
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+
+    if let Value::Object(map) = target {
+        for (key, value) in patch {
+            if value.is_null() {
+                map.remove(&key);
+            } else {
+                merge_patch(map.entry(key).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+/// Merge patch that turns `base` into `target`. Arrays are replaced whole,
+/// keys missing from `target` are removed.
+pub fn diff(base: &Value, target: &Value) -> Value {
+    let (Value::Object(base), Value::Object(target)) = (base, target) else {
+        return target.clone();
+    };
+
+    let mut patch = Map::new();
+
+    for (key, value) in target {
+        match base.get(key) {
+            Some(old) if old == value => {}
+            Some(old) => {
+                patch.insert(key.clone(), diff(old, value));
+            }
+            None => {
+                patch.insert(key.clone(), value.clone());
+            }
+        }
+    }
+
+    for key in base.keys() {
+        if !target.contains_key(key) {
+            patch.insert(key.clone(), Value::Null);
+        }
+    }
+
+    Value::Object(patch)
+}
+
+/// Lists that accumulate across layers instead of being replaced.
+const ACCUMULATED_LISTS: &[&str] = &["partitions", "overrides"];
+
+/// Rebases the accumulated lists in a `patch` built against the `effective`
+/// config on the entries `file` itself holds, so entries that came from a
+/// drop-in are never copied into the main file.
+pub fn rebase_lists(patch: &mut Value, effective: &Value, file: &Value) {
+    let Value::Object(patch) = patch else {
+        return;
+    };
+
+    for key in ACCUMULATED_LISTS {
+        let (Some(Value::Array(next)), Some(Value::Array(previous))) =
+            (patch.get(*key), effective.get(*key))
+        else {
+            continue;
+        };
+
+        let mut rebased: Vec<Value> = file
+            .get(*key)
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter(|item| next.contains(item))
+            .cloned()
+            .collect();
+        for item in next {
+            if !previous.contains(item) && !rebased.contains(item) {
+                rebased.push(item.clone());
+            }
+        }
+
+        patch.insert(key.to_string(), Value::Array(rebased));
+    }
+}
+
+/// Applies `patch` to the file at `path`. Keys are addressed as they appear
+/// in the effective config, but only `path` is written, so drop-ins stay
+/// untouched and removing a key falls back to the drop-in or default value.
+pub fn apply(
+    path: &Path,
+    dropin_dir: Option<&Path>,
+    patch: Value,
+    expected_revision: Option<&str>,
+) -> Result<PatchOutcome> {
+    if !patch.is_object() {
+        bail!("config patch must be an object");
+    }
+
+    check_revision(path, expected_revision)?;
+
+    let table = load_for_update(path)?;
+
+    let patched_keys: Vec<String> = patch
+        .as_object()
+        .map(|map| map.keys().cloned().collect())
+        .unwrap_or_default();
+
+    let mut document = serde_json::to_value(&table).context("failed to convert config")?;
+    merge_patch(&mut document, patch);
+    let table: toml::Table =
+        serde_json::from_value(document).context("patched config is not a valid table")?;
+
+    let (_, sources) = Config::with_dropins(path, table.clone(), dropin_dir)
+        .context("patched config is invalid")?;
+
+    Config::save_table(path, &table).context("failed to save patched config")?;
+
+    let main = path.display().to_string();
+    let shadowed = sources
+        .iter()
+        .filter(|(key, source)| {
+            **source != main
+                && patched_keys.iter().any(|patched| {
+                    key.strip_prefix(patched.as_str())
+                        .is_some_and(|rest| rest.is_empty() || rest.starts_with(['.', '[']))
+                })
+        })
+        .map(|(key, _)| key.clone())
+        .collect();
+
+    Ok(PatchOutcome {
+        revision: revision(path)?,
+        shadowed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn diff_round_trips_through_merge_patch() {
+        let base = json!({ "a": 1, "b": { "c": 2, "d": 3 }, "e": [1, 2] });
+        let target = json!({ "a": 1, "b": { "c": 4 }, "e": [2] });
+
+        let patch = diff(&base, &target);
+        assert_eq!(patch, json!({ "b": { "c": 4, "d": null }, "e": [2] }));
+
+        let mut patched = base;
+        merge_patch(&mut patched, patch);
+        assert_eq!(patched, target);
+    }
+
+    #[test]
+    fn rebased_lists_keep_dropin_entries_out() {
+        let effective = json!({ "partitions": ["own", "dropin", "gone"] });
+        let file = json!({ "partitions": ["own", "gone"] });
+        let mut patch = json!({ "partitions": ["own", "dropin", "new"], "moduledir": "/x" });
+
+        rebase_lists(&mut patch, &effective, &file);
+
+        assert_eq!(
+            patch,
+            json!({ "partitions": ["own", "new"], "moduledir": "/x" })
+        );
+    }
+}
@@ -0,0 +1,368 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::path::Path;
+
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+
+use crate::{
+    conf::{
+        config::{Config, MountMode, OverlayMode, RuleOverride},
+        migration,
+    },
+    defs,
+    sys::kernel::KernelCapabilities,
+    utils,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ValidationIssue {
+    pub path: String,
+    pub severity: Severity,
+    pub message: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ValidationReport {
+    pub valid: bool,
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    fn push(&mut self, path: impl Into<String>, severity: Severity, message: impl Into<String>) {
+        self.issues.push(ValidationIssue {
+            path: path.into(),
+            severity,
+            message: message.into(),
+        });
+    }
+
+    fn finish(mut self) -> Self {
+        self.valid = !self.issues.iter().any(|i| i.severity == Severity::Error);
+        self
+    }
+}
+
+const PROFILE_KEYS: &[&str] = &[
+    "moduledir",
+    "mountsource",
+    "partitions",
+    "overlay_mode",
+    "disable_umount",
+    "allow_umount_coexistence",
+    "default_mode",
+    "partition_rules",
+    "priority",
+    "rules",
+];
+const RULE_OVERRIDE_KEYS: &[&str] = &["when", "default_mode", "paths"];
+
+fn known_keys() -> Vec<String> {
+    match serde_json::to_value(Config::default()) {
+        Ok(Value::Object(map)) => map.keys().cloned().collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// `caps` are the kernel capabilities to check the config against, checks
+/// that need them are skipped without.
+pub fn validate_toml(content: &str, caps: Option<&KernelCapabilities>) -> ValidationReport {
+    let mut report = ValidationReport::default();
+
+    let mut table: toml::Table = match toml::from_str(content) {
+        Ok(table) => table,
+        Err(e) => {
+            report.push("", Severity::Error, e.to_string());
+            return report.finish();
+        }
+    };
+
+    if let Err(e) = migration::migrate(&mut table) {
+        report.push("config_version", Severity::Error, format!("{:#}", e));
+        return report.finish();
+    }
+
+    match serde_json::to_value(&table) {
+        Ok(Value::Object(map)) => validate_map(&map, caps, report),
+        Ok(_) => report.finish(),
+        Err(e) => {
+            report.push("", Severity::Error, e.to_string());
+            report.finish()
+        }
+    }
+}
+
+pub fn validate_json(content: &[u8], caps: Option<&KernelCapabilities>) -> ValidationReport {
+    let mut report = ValidationReport::default();
+
+    match serde_json::from_slice::<Value>(content) {
+        Ok(Value::Object(map)) => validate_map(&map, caps, report),
+        Ok(_) => {
+            report.push("", Severity::Error, "config payload must be an object");
+            report.finish()
+        }
+        Err(e) => {
+            report.push("", Severity::Error, e.to_string());
+            report.finish()
+        }
+    }
+}
+
+fn validate_map(
+    map: &Map<String, Value>,
+    caps: Option<&KernelCapabilities>,
+    mut report: ValidationReport,
+) -> ValidationReport {
+    let top_level = known_keys();
+
+    // Each key is deserialized on its own so a type error points at the key
+    // that caused it instead of failing the whole document.
+    for (key, value) in map {
+        if !top_level.contains(key) {
+            report.push(key.as_str(), Severity::Error, "unknown key");
+            continue;
+        }
+        if key == "rules" {
+            continue;
+        }
+        if let Err(e) = serde_json::from_value::<Config>(json!({ key.as_str(): value })) {
+            report.push(key.as_str(), Severity::Error, e.to_string());
+        }
+    }
+
+    if let Some(rules) = map.get("rules") {
+        validate_rules(rules, map, &mut report);
+    }
+
+    if let Some(Value::Object(profiles)) = map.get("profiles") {
+        for (name, profile) in profiles {
+            validate_profile(&format!("profiles.{}", name), profile, &[], &mut report);
+        }
+    }
+
+    if let Some(Value::Array(overrides)) = map.get("overrides") {
+        for (i, entry) in overrides.iter().enumerate() {
+            validate_profile(&format!("overrides[{}]", i), entry, &["when"], &mut report);
+        }
+    }
+
+    let Ok(config) = serde_json::from_value::<Config>(Value::Object(map.clone())) else {
+        return report.finish();
+    };
+
+    if !config.moduledir.is_dir() {
+        report.push(
+            "moduledir",
+            Severity::Error,
+            format!("{} does not exist", config.moduledir.display()),
+        );
+    }
+
+    for (i, partition) in config.partitions.iter().enumerate() {
+        if !defs::BUILTIN_PARTITIONS.contains(&partition.as_str())
+            && !Path::new("/").join(partition).is_dir()
+        {
+            report.push(
+                format!("partitions[{}]", i),
+                Severity::Warning,
+                format!(
+                    "'{}' is not a builtin partition and /{} does not exist",
+                    partition, partition
+                ),
+            );
+        }
+    }
+
+    for partition in config.partition_rules.keys() {
+        if !defs::BUILTIN_PARTITIONS.contains(&partition.as_str())
+            && !config.partitions.contains(partition)
+        {
+            report.push(
+                format!("partition_rules.{}", partition),
+                Severity::Warning,
+                format!("'{}' is not a builtin or configured partition", partition),
+            );
+        }
+    }
+
+    for id in config.priority.keys() {
+        if !config.moduledir.join(id).is_dir() {
+            report.push(
+                format!("priority.{}", id),
+                Severity::Warning,
+                format!(
+                    "module '{}' is not installed in {}",
+                    id,
+                    config.moduledir.display()
+                ),
+            );
+        }
+    }
+
+    for (pattern, id) in &config.conflict_resolution {
+        if !config.moduledir.join(id).is_dir() {
+            report.push(
+                format!("conflict_resolution.{}", pattern),
+                Severity::Warning,
+                format!(
+                    "module '{}' is not installed in {}",
+                    id,
+                    config.moduledir.display()
+                ),
+            );
+        }
+    }
+
+    for (i, pattern) in config.sync.exclude.iter().enumerate() {
+        if utils::glob_match_prefix(pattern, "module.prop") {
+            report.push(
+                format!("sync.exclude[{}]", i),
+                Severity::Warning,
+                format!("'{}' matches module.prop, which is always synced", pattern),
+            );
+        }
+    }
+
+    match config.overlay_mode {
+        OverlayMode::Erofs if caps.is_some_and(|caps| !caps.erofs) => report.push(
+            "overlay_mode",
+            Severity::Error,
+            "erofs is not supported by this kernel",
+        ),
+        OverlayMode::Tmpfs if caps.is_some_and(|caps| !caps.tmpfs_xattr) => report.push(
+            "overlay_mode",
+            Severity::Warning,
+            "tmpfs does not support trusted xattrs on this kernel, ext4 will be used instead",
+        ),
+        _ => {}
+    }
+
+    if let Some(name) = &config.active_profile
+        && !config.profiles.contains_key(name)
+    {
+        report.push(
+            "active_profile",
+            Severity::Error,
+            format!("profile '{}' is not defined", name),
+        );
+    }
+
+    if config.disable_umount && config.allow_umount_coexistence {
+        report.push(
+            "allow_umount_coexistence",
+            Severity::Warning,
+            "has no effect while disable_umount is true",
+        );
+    }
+
+    report.finish()
+}
+
+fn validate_rules(rules: &Value, map: &Map<String, Value>, report: &mut ValidationReport) {
+    let Value::Object(rules) = rules else {
+        report.push("rules", Severity::Error, "rules must be a table");
+        return;
+    };
+
+    let moduledir = map
+        .get("moduledir")
+        .and_then(|v| v.as_str())
+        .unwrap_or(defs::MODULES_DIR);
+
+    for (id, rule) in rules {
+        let base = format!("rules.{}", id);
+
+        if let Err(e) = utils::validate_module_id(id) {
+            report.push(base.as_str(), Severity::Error, format!("{:#}", e));
+        } else if !Path::new(moduledir).join(id).is_dir() {
+            report.push(
+                base.as_str(),
+                Severity::Warning,
+                format!("module '{}' is not installed in {}", id, moduledir),
+            );
+        }
+
+        validate_module_rules(&base, rule, report);
+    }
+}
+
+fn validate_module_rules(base: &str, rule: &Value, report: &mut ValidationReport) {
+    let Value::Object(rule) = rule else {
+        report.push(base, Severity::Error, "module rules must be a table");
+        return;
+    };
+
+    for (key, value) in rule {
+        let path = format!("{}.{}", base, key);
+        match key.as_str() {
+            "default_mode" => {
+                if let Err(e) = serde_json::from_value::<MountMode>(value.clone()) {
+                    report.push(path, Severity::Error, e.to_string());
+                }
+            }
+            "paths" => {
+                let Value::Object(paths) = value else {
+                    report.push(path, Severity::Error, "paths must be a table");
+                    continue;
+                };
+                for (pattern, mode) in paths {
+                    if let Err(e) = serde_json::from_value::<MountMode>(mode.clone()) {
+                        report.push(
+                            format!("{}.\"{}\"", path, pattern),
+                            Severity::Error,
+                            e.to_string(),
+                        );
+                    }
+                }
+            }
+            "overrides" => {
+                if let Err(e) = serde_json::from_value::<Vec<RuleOverride>>(value.clone()) {
+                    report.push(path.as_str(), Severity::Error, e.to_string());
+                }
+                for (i, entry) in value.as_array().into_iter().flatten().enumerate() {
+                    let Some(entry) = entry.as_object() else {
+                        continue;
+                    };
+                    for key in entry.keys() {
+                        if !RULE_OVERRIDE_KEYS.contains(&key.as_str()) {
+                            report.push(
+                                format!("{}[{}].{}", path, i, key),
+                                Severity::Error,
+                                "unknown key",
+                            );
+                        }
+                    }
+                }
+            }
+            _ => report.push(path, Severity::Error, "unknown key"),
+        }
+    }
+}
+
+// Profiles and overrides are checked key by key since `ConfigOverride`
+// flattens its settings, which rules out `deny_unknown_fields`.
+fn validate_profile(base: &str, profile: &Value, extra: &[&str], report: &mut ValidationReport) {
+    let Value::Object(profile) = profile else {
+        return;
+    };
+
+    for key in profile.keys() {
+        if !PROFILE_KEYS.contains(&key.as_str()) && !extra.contains(&key.as_str()) {
+            report.push(format!("{}.{}", base, key), Severity::Error, "unknown key");
+        }
+    }
+
+    if let Some(Value::Object(rules)) = profile.get("rules") {
+        for (id, rule) in rules {
+            validate_module_rules(&format!("{}.rules.{}", base, id), rule, report);
+        }
+    }
+}
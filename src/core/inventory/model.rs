@@ -1,12 +1,12 @@
 use std::{
-    collections::HashSet,
+    collections::{BTreeMap, HashSet},
     fs::{self},
     io::{BufRead, BufReader},
     path::Path,
     sync::OnceLock,
 };
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use regex_lite::Regex;
 use serde::Serialize;
 
@@ -19,39 +19,72 @@ use crate::{
 
 static MODULE_PROP_REGEX: OnceLock<Regex> = OnceLock::new();
 
-#[derive(Default)]
-struct ModuleProp {
-    name: String,
-    version: String,
-    author: String,
-    description: String,
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ModuleProp {
+    pub id: Option<String>,
+    pub name: String,
+    pub version: String,
+    pub version_code: Option<i64>,
+    pub author: String,
+    pub description: String,
+    pub update_json: Option<String>,
+    /// `requires`, `conflicts` and `after`, as comma separated module ids.
+    pub dependencies: inventory::Dependencies,
+    /// Keys not listed above, and a `versionCode` that is not an integer.
+    pub extra: BTreeMap<String, String>,
 }
 
-impl From<&Path> for ModuleProp {
-    fn from(path: &Path) -> Self {
+fn split_ids(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|id| id.trim().to_string())
+        .filter(|id| !id.is_empty())
+        .collect()
+}
+
+impl ModuleProp {
+    pub fn parse(content: &str) -> Self {
         let mut prop = ModuleProp::default();
         let re = MODULE_PROP_REGEX.get_or_init(|| {
             Regex::new(r"^([a-zA-Z0-9_.]+)=(.*)$").expect("Failed to compile module prop regex")
         });
 
-        if let Ok(file) = fs::File::open(path) {
-            for line in BufReader::new(file).lines().map_while(Result::ok) {
-                if let Some(caps) = re.captures(line.trim()) {
-                    let k = caps.get(1).map_or("", |m| m.as_str());
-                    let v = caps.get(2).map_or("", |m| m.as_str());
-
-                    match k {
-                        "name" => prop.name = v.to_string(),
-                        "version" => prop.version = v.to_string(),
-                        "author" => prop.author = v.to_string(),
-                        "description" => prop.description = v.to_string(),
-                        _ => {}
+        for line in content.lines() {
+            let Some(caps) = re.captures(line.trim()) else {
+                continue;
+            };
+            let k = caps.get(1).map_or("", |m| m.as_str());
+            let v = caps.get(2).map_or("", |m| m.as_str()).to_string();
+
+            match k {
+                "id" => prop.id = Some(v),
+                "name" => prop.name = v,
+                "version" => prop.version = v,
+                "versionCode" => match v.trim().parse() {
+                    Ok(code) => prop.version_code = Some(code),
+                    Err(_) => {
+                        prop.extra.insert(k.to_string(), v);
                     }
+                },
+                "author" => prop.author = v,
+                "description" => prop.description = v,
+                "updateJson" => prop.update_json = Some(v),
+                "requires" => prop.dependencies.requires = split_ids(&v),
+                "conflicts" => prop.dependencies.conflicts = split_ids(&v),
+                "after" => prop.dependencies.after = split_ids(&v),
+                _ => {
+                    prop.extra.insert(k.to_string(), v);
                 }
             }
         }
         prop
     }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        Ok(Self::parse(&content))
+    }
 }
 
 #[derive(Serialize)]
@@ -61,15 +94,19 @@ struct ModuleInfo {
     version: String,
     author: String,
     description: String,
+    version_code: Option<i64>,
+    update_json: Option<String>,
     mode: String,
     is_mounted: bool,
+    exclusion: Option<String>,
+    priority: i32,
+    /// Position in the layer order, 0 is the topmost layer.
+    layer: usize,
     rules: config::ModuleRules,
 }
 
 impl ModuleInfo {
-    fn new(m: inventory::Module, mounted_set: &HashSet<&str>) -> Self {
-        let prop = ModuleProp::from(m.source_path.join("module.prop").as_path());
-
+    fn new(m: inventory::Module, layer: usize, mounted_set: &HashSet<&str>) -> Self {
         let mode_str = match m.rules.default_mode {
             MountMode::Overlay => "auto",
             MountMode::Magic => "magic",
@@ -79,11 +116,16 @@ impl ModuleInfo {
         Self {
             is_mounted: mounted_set.contains(m.id.as_str()),
             id: m.id,
-            name: prop.name,
-            version: prop.version,
-            author: prop.author,
-            description: prop.description,
+            name: m.prop.name,
+            version: m.prop.version,
+            author: m.prop.author,
+            description: m.prop.description,
+            version_code: m.prop.version_code,
+            update_json: m.prop.update_json,
             mode: mode_str.to_string(),
+            exclusion: m.exclusion,
+            priority: m.priority,
+            layer,
             rules: m.rules,
         }
     }
@@ -101,9 +143,11 @@ pub fn print_list(config: &config::Config) -> Result<()> {
         .map(|s| s.as_str())
         .collect();
 
+    // `scan` returns modules in layer order
     let infos: Vec<ModuleInfo> = modules
         .into_iter()
-        .map(|m| ModuleInfo::new(m, &mounted_ids))
+        .enumerate()
+        .map(|(layer, m)| ModuleInfo::new(m, layer, &mounted_ids))
         .collect();
 
     println!("{}", serde_json::to_string(&infos)?);
@@ -1,25 +1,141 @@
 use std::{
-    collections::HashMap,
+    cmp::Ordering,
+    collections::{BTreeSet, HashMap},
     fs,
     path::{Path, PathBuf},
 };
 
 use anyhow::Result;
 use rayon::prelude::*;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
 
+use super::model::ModuleProp;
 use crate::{
-    conf::config::{self, ModuleRules, MountMode},
+    conf::config::{self, ModuleRules, MountMode, RuleOverride},
+    core::ops::planner::{DiagnosticIssue, DiagnosticLevel},
     defs,
+    sys::props::{self, PropertySource},
+    utils,
 };
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Default)]
 struct PartialRules {
     default_mode: Option<MountMode>,
     paths: Option<HashMap<String, MountMode>>,
+    #[serde(default)]
+    overrides: Vec<RuleOverride>,
+    priority: Option<i32>,
+    #[serde(flatten)]
+    dependencies: Dependencies,
 }
 
-fn load_module_rules(module_dir: &Path, module_id: &str, cfg: &config::Config) -> ModuleRules {
+/// Relations to other modules, declared in `hybrid_rules.json` or as
+/// comma separated `module.prop` keys.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Dependencies {
+    /// Modules that must be active and layered below this one.
+    #[serde(default)]
+    pub requires: Vec<String>,
+    /// Modules that must not be active together with this one.
+    #[serde(default)]
+    pub conflicts: Vec<String>,
+    /// Modules this one is layered above when they are active.
+    #[serde(default)]
+    pub after: Vec<String>,
+}
+
+impl Dependencies {
+    fn extend(&mut self, other: Dependencies) {
+        for (list, items) in [
+            (&mut self.requires, other.requires),
+            (&mut self.conflicts, other.conflicts),
+            (&mut self.after, other.after),
+        ] {
+            for item in items {
+                if !list.contains(&item) {
+                    list.push(item);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize)]
+pub enum RuleSource {
+    #[default]
+    #[serde(rename = "default_mode")]
+    DefaultMode,
+    #[serde(rename = "hybrid_rules.json")]
+    HybridRules,
+    #[serde(rename = "config.partition_rules")]
+    PartitionRules,
+    #[serde(rename = "config.rules")]
+    ConfigRules,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RuleOrigins {
+    pub default_mode: RuleSource,
+    pub paths: HashMap<String, RuleSource>,
+}
+
+impl RuleOrigins {
+    pub fn source_of(&self, rules: &ModuleRules, relative_path: &str) -> RuleSource {
+        match rules.matching_rule(relative_path) {
+            Some((pattern, _)) => self.paths.get(pattern).copied().unwrap_or_default(),
+            None => self.default_mode,
+        }
+    }
+}
+
+fn apply_rule_overrides(
+    rules: &mut ModuleRules,
+    origins: &mut RuleOrigins,
+    overrides: &[RuleOverride],
+    source: RuleSource,
+    props: &dyn PropertySource,
+) {
+    for entry in overrides.iter().filter(|o| o.when.matches(props)) {
+        if let Some(mode) = &entry.default_mode {
+            rules.default_mode = mode.clone();
+            origins.default_mode = source;
+        }
+        rules.paths.extend(entry.paths.clone());
+        origins
+            .paths
+            .extend(entry.paths.keys().map(|k| (k.clone(), source)));
+    }
+}
+
+fn read_hybrid_rules(module_dir: &Path, module_id: &str) -> Option<PartialRules> {
+    let internal_config = module_dir.join("hybrid_rules.json");
+
+    if !internal_config.exists() {
+        return None;
+    }
+
+    match fs::read_to_string(&internal_config) {
+        Ok(content) => match serde_json::from_str::<PartialRules>(&content) {
+            Ok(partial) => Some(partial),
+            Err(e) => {
+                log::warn!("Failed to parse rules for module '{}': {}", module_id, e);
+                None
+            }
+        },
+        Err(e) => {
+            log::warn!("Failed to read rule file for '{}': {}", module_id, e);
+            None
+        }
+    }
+}
+
+fn load_module_rules(
+    partial: &PartialRules,
+    module_id: &str,
+    cfg: &config::Config,
+    props: &dyn PropertySource,
+) -> (ModuleRules, RuleOrigins) {
     let mut rules = ModuleRules {
         default_mode: match cfg.default_mode {
             config::DefaultMode::Overlay => MountMode::Overlay,
@@ -27,86 +143,607 @@ fn load_module_rules(module_dir: &Path, module_id: &str, cfg: &config::Config) -
         },
         ..Default::default()
     };
+    let mut origins = RuleOrigins::default();
 
-    let internal_config = module_dir.join("hybrid_rules.json");
+    // Partition rules go in first, so a module rule for the same pattern
+    // replaces them and a more specific one wins by length.
+    for (pattern, mode) in cfg.partition_rule_patterns() {
+        origins
+            .paths
+            .insert(pattern.clone(), RuleSource::PartitionRules);
+        rules.paths.insert(pattern, mode);
+    }
 
-    if internal_config.exists() {
-        match fs::read_to_string(&internal_config) {
-            Ok(content) => match serde_json::from_str::<PartialRules>(&content) {
-                Ok(partial) => {
-                    if let Some(mode) = partial.default_mode {
-                        rules.default_mode = mode;
-                    }
-                    if let Some(paths) = partial.paths {
-                        rules.paths = paths;
-                    }
-                }
-                Err(e) => {
-                    log::warn!("Failed to parse rules for module '{}': {}", module_id, e)
-                }
-            },
-            Err(e) => log::warn!("Failed to read rule file for '{}': {}", module_id, e),
-        }
+    if let Some(mode) = &partial.default_mode {
+        rules.default_mode = mode.clone();
+        origins.default_mode = RuleSource::HybridRules;
+    }
+    if let Some(paths) = &partial.paths {
+        origins
+            .paths
+            .extend(paths.keys().map(|k| (k.clone(), RuleSource::HybridRules)));
+        rules.paths.extend(paths.clone());
     }
+    apply_rule_overrides(
+        &mut rules,
+        &mut origins,
+        &partial.overrides,
+        RuleSource::HybridRules,
+        props,
+    );
 
     if let Some(global_rules) = cfg.rules.get(module_id) {
         rules.default_mode = global_rules.default_mode.clone();
+        origins.default_mode = RuleSource::ConfigRules;
         rules.paths.extend(global_rules.paths.clone());
+        origins.paths.extend(
+            global_rules
+                .paths
+                .keys()
+                .map(|k| (k.clone(), RuleSource::ConfigRules)),
+        );
+        apply_rule_overrides(
+            &mut rules,
+            &mut origins,
+            &global_rules.overrides,
+            RuleSource::ConfigRules,
+            props,
+        );
     }
 
-    rules
+    (rules, origins)
 }
 
 #[derive(Debug, Clone)]
 pub struct Module {
     pub id: String,
     pub source_path: PathBuf,
+    pub prop: ModuleProp,
     pub rules: ModuleRules,
+    pub rule_origins: RuleOrigins,
+    pub priority: i32,
+    pub dependencies: Dependencies,
+    /// Why the module is left out of the mount plan, set when its
+    /// dependencies cannot be met.
+    pub exclusion: Option<String>,
+    /// Module-relative files that lose to the winner pinned in
+    /// `conflict_resolution` and are left out of the synced copy.
+    pub masked: BTreeSet<String>,
 }
 
-pub fn scan(source_dir: &Path, cfg: &config::Config) -> Result<Vec<Module>> {
+impl Module {
+    pub fn content_path(&self, storage_root: &Path) -> PathBuf {
+        let synced = storage_root.join(&self.id);
+        if synced.exists() {
+            synced
+        } else {
+            self.source_path.clone()
+        }
+    }
+}
+
+/// Order in which modules are stacked, topmost (winning) layer first:
+/// higher priority first, then descending id.
+pub fn layer_order(a: &Module, b: &Module) -> Ordering {
+    b.priority.cmp(&a.priority).then_with(|| b.id.cmp(&a.id))
+}
+
+/// Module directories that are neither ignored nor disabled, before their
+/// module.prop is read.
+fn module_dirs(source_dir: &Path) -> Result<Vec<(String, PathBuf)>> {
     if !source_dir.exists() {
         return Ok(Vec::new());
     }
 
-    let dir_entries = fs::read_dir(source_dir)?.collect::<std::io::Result<Vec<_>>>()?;
+    let mut dirs = Vec::new();
 
-    let mut modules: Vec<Module> = dir_entries
-        .into_par_iter()
-        .filter_map(|entry| {
-            let path = entry.path();
+    for entry in fs::read_dir(source_dir)? {
+        let path = entry?.path();
 
-            if !path.is_dir() {
-                return None;
-            }
+        if !path.is_dir() {
+            continue;
+        }
 
-            let id = entry.file_name().to_string_lossy().to_string();
+        let Some(id) = path.file_name().map(|n| n.to_string_lossy().to_string()) else {
+            continue;
+        };
 
-            if matches!(
-                id.as_str(),
-                "hybrid-mount" | "lost+found" | ".git" | ".idea" | ".vscode"
-            ) {
-                return None;
-            }
+        if matches!(
+            id.as_str(),
+            "hybrid-mount" | "lost+found" | ".git" | ".idea" | ".vscode"
+        ) {
+            continue;
+        }
 
-            if path.join(defs::DISABLE_FILE_NAME).exists()
-                || path.join(defs::REMOVE_FILE_NAME).exists()
-                || path.join(defs::SKIP_MOUNT_FILE_NAME).exists()
-            {
-                return None;
-            }
+        if path.join(defs::DISABLE_FILE_NAME).exists()
+            || path.join(defs::REMOVE_FILE_NAME).exists()
+            || path.join(defs::SKIP_MOUNT_FILE_NAME).exists()
+        {
+            continue;
+        }
+
+        dirs.push((id, path));
+    }
 
-            let rules = load_module_rules(&path, &id, cfg);
+    Ok(dirs)
+}
+
+fn load_prop(module_dir: &Path) -> Result<ModuleProp> {
+    let prop = ModuleProp::load(&module_dir.join("module.prop"))?;
+
+    if let Some(id) = &prop.id {
+        utils::validate_module_id(id)?;
+    }
+
+    Ok(prop)
+}
+
+pub fn scan(source_dir: &Path, cfg: &config::Config) -> Result<Vec<Module>> {
+    let props = props::source();
+
+    let mut modules: Vec<Module> = module_dirs(source_dir)?
+        .into_par_iter()
+        .filter_map(|(id, path)| {
+            let prop = match load_prop(&path) {
+                Ok(prop) => prop,
+                Err(e) => {
+                    log::warn!("Skipping module {}: {:#}", id, e);
+                    return None;
+                }
+            };
+
+            let partial = read_hybrid_rules(&path, &id).unwrap_or_default();
+            let (rules, rule_origins) = load_module_rules(&partial, &id, cfg, props);
+            let priority = cfg
+                .priority
+                .get(&id)
+                .copied()
+                .or(partial.priority)
+                .unwrap_or(0);
+            let mut dependencies = prop.dependencies.clone();
+            dependencies.extend(partial.dependencies);
 
             Some(Module {
                 id,
                 source_path: path,
+                prop,
                 rules,
+                rule_origins,
+                priority,
+                dependencies,
+                exclusion: None,
+                masked: BTreeSet::new(),
             })
         })
         .collect();
 
-    modules.sort_by(|a, b| b.id.cmp(&a.id));
+    modules.sort_by(layer_order);
+    let mut modules = resolve_dependencies(modules);
+
+    if !cfg.conflict_resolution.is_empty() {
+        let masked: Vec<BTreeSet<String>> = modules
+            .par_iter()
+            .map(|module| masked_paths(module, &modules, cfg))
+            .collect();
+        for (module, masked) in modules.iter_mut().zip(masked) {
+            module.masked = masked;
+        }
+    }
 
     Ok(modules)
 }
+
+/// Works out which modules miss a requirement and which conflict with an
+/// active module, as the id of the module that is missing or conflicting.
+///
+/// Requirement exclusions only accumulate, while conflicts are checked again
+/// each round against the modules that are left, so a module dropped for a
+/// missing requirement no longer pushes out the modules it conflicts with.
+/// A requirement that is only inactive because of a conflict is acted on
+/// last, as that conflict may still go away.
+fn exclusions(modules: &[Module]) -> (Vec<Option<String>>, Vec<Option<String>>) {
+    let index: HashMap<&str, usize> = modules
+        .iter()
+        .enumerate()
+        .map(|(i, m)| (m.id.as_str(), i))
+        .collect();
+    let mut required: Vec<Option<String>> = vec![None; modules.len()];
+
+    loop {
+        // Conflicts are checked from the topmost layer down, so of two modules
+        // that name each other only the upper one is dropped.
+        let mut conflicting: Vec<Option<String>> = vec![None; modules.len()];
+        for (i, module) in modules.iter().enumerate() {
+            if required[i].is_some() {
+                continue;
+            }
+            conflicting[i] = module
+                .dependencies
+                .conflicts
+                .iter()
+                .find(|id| {
+                    **id != module.id
+                        && index
+                            .get(id.as_str())
+                            .is_some_and(|&j| required[j].is_none() && conflicting[j].is_none())
+                })
+                .cloned();
+        }
+
+        let active = |j: usize| required[j].is_none() && conflicting[j].is_none();
+        let (mut missing, mut conflicted) = (Vec::new(), Vec::new());
+        for (i, module) in modules.iter().enumerate().filter(|&(i, _)| active(i)) {
+            let Some(id) = module
+                .dependencies
+                .requires
+                .iter()
+                .find(|id| !index.get(id.as_str()).is_some_and(|&j| active(j)))
+            else {
+                continue;
+            };
+            match index.get(id.as_str()) {
+                Some(&j) if required[j].is_none() => conflicted.push((i, id.clone())),
+                _ => missing.push((i, id.clone())),
+            }
+        }
+
+        let dropped = if missing.is_empty() {
+            conflicted
+        } else {
+            missing
+        };
+        if dropped.is_empty() {
+            return (required, conflicting);
+        }
+        for (i, id) in dropped {
+            required[i] = Some(id);
+        }
+    }
+}
+
+/// Excludes modules whose declared conflicts are active or whose
+/// requirements are missing, then orders the list so every module sits above
+/// the modules it requires or comes after. Unrelated modules keep the
+/// priority order of `modules`.
+fn resolve_dependencies(mut modules: Vec<Module>) -> Vec<Module> {
+    let (required, conflicting) = exclusions(&modules);
+    for (module, (required, conflicting)) in modules
+        .iter_mut()
+        .zip(required.into_iter().zip(conflicting))
+    {
+        module.exclusion = required
+            .map(|id| format!("requires module '{}' which is not active", id))
+            .or_else(|| conflicting.map(|id| format!("conflicts with active module '{}'", id)));
+    }
+
+    for module in modules.iter().filter(|m| m.exclusion.is_some()) {
+        log::warn!(
+            "Module {} excluded: {}",
+            module.id,
+            module.exclusion.as_deref().unwrap_or_default()
+        );
+    }
+
+    let index: HashMap<&str, usize> = modules
+        .iter()
+        .enumerate()
+        .map(|(i, m)| (m.id.as_str(), i))
+        .collect();
+
+    // below[i] lists the modules that have to be layered under module i.
+    let mut below: Vec<Vec<usize>> = vec![Vec::new(); modules.len()];
+    let mut pending_above = vec![0usize; modules.len()];
+    for (i, module) in modules.iter().enumerate() {
+        if module.exclusion.is_some() {
+            continue;
+        }
+        for id in module
+            .dependencies
+            .requires
+            .iter()
+            .chain(&module.dependencies.after)
+        {
+            if let Some(&j) = index.get(id.as_str())
+                && j != i
+                && modules[j].exclusion.is_none()
+                && !below[i].contains(&j)
+            {
+                below[i].push(j);
+                pending_above[j] += 1;
+            }
+        }
+    }
+
+    // Excluded modules are not layered at all and go to the end.
+    let mut placed: Vec<bool> = modules.iter().map(|m| m.exclusion.is_some()).collect();
+    let mut order = Vec::with_capacity(modules.len());
+    while let Some(first) = (0..modules.len()).find(|&i| !placed[i]) {
+        let next = (first..modules.len())
+            .find(|&i| !placed[i] && pending_above[i] == 0)
+            .unwrap_or_else(|| {
+                log::warn!(
+                    "Module {} is part of an ordering cycle, using its priority order",
+                    modules[first].id
+                );
+                first
+            });
+        placed[next] = true;
+        for &j in &below[next] {
+            pending_above[j] = pending_above[j].saturating_sub(1);
+        }
+        order.push(next);
+    }
+    order.extend((0..modules.len()).filter(|&i| modules[i].exclusion.is_some()));
+
+    let mut slots: Vec<Option<Module>> = modules.into_iter().map(Some).collect();
+    order.into_iter().filter_map(|i| slots[i].take()).collect()
+}
+
+/// Problems with the module.prop of the modules in `source_dir`, including
+/// the ones `scan` had to skip.
+pub fn prop_diagnostics(source_dir: &Path, modules: &[Module]) -> Vec<DiagnosticIssue> {
+    let mut issues = Vec::new();
+
+    for (id, path) in module_dirs(source_dir).unwrap_or_default() {
+        if let Err(e) = load_prop(&path) {
+            issues.push(DiagnosticIssue {
+                level: DiagnosticLevel::Critical,
+                context: id,
+                message: format!("Module is skipped: {:#}", e),
+            });
+        }
+    }
+
+    for module in modules {
+        let mut warn = |message: String| {
+            issues.push(DiagnosticIssue {
+                level: DiagnosticLevel::Warning,
+                context: module.id.clone(),
+                message,
+            })
+        };
+
+        match &module.prop.id {
+            None => warn("module.prop does not declare an id".to_string()),
+            Some(id) if *id != module.id => warn(format!(
+                "module.prop declares id '{}' but the module directory is '{}'",
+                id, module.id
+            )),
+            Some(_) => {}
+        }
+
+        if let Some(code) = module.prop.extra.get("versionCode") {
+            warn(format!(
+                "module.prop versionCode '{}' is not an integer",
+                code
+            ));
+        }
+    }
+
+    issues
+}
+
+/// Files of `module` covered by a `conflict_resolution` entry that pins
+/// another module which provides the same path.
+fn masked_paths(module: &Module, modules: &[Module], cfg: &config::Config) -> BTreeSet<String> {
+    let relative = |path: &Path| {
+        path.strip_prefix(&module.source_path)
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_default()
+    };
+
+    let walker = WalkDir::new(&module.source_path)
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(|entry| {
+            let rel = relative(entry.path());
+            cfg.conflict_resolution.keys().any(|pattern| {
+                utils::glob_match_prefix(pattern, &rel)
+                    || utils::glob_may_match_below(pattern, &rel)
+            })
+        });
+
+    let mut masked = BTreeSet::new();
+    for entry in walker.flatten() {
+        if entry.file_type().is_dir() {
+            continue;
+        }
+
+        let rel = relative(entry.path());
+        let Some((_, winner)) = cfg.conflict_winner(&rel) else {
+            continue;
+        };
+
+        // Only mask what the winner really provides, otherwise nobody would.
+        if winner != module.id
+            && modules.iter().any(|m| {
+                m.id == winner
+                    && m.exclusion.is_none()
+                    && m.rules.get_mode(&rel) != MountMode::Ignore
+                    && m.source_path.join(&rel).symlink_metadata().is_ok()
+            })
+        {
+            masked.insert(rel);
+        }
+    }
+
+    masked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module(id: &str, requires: &[&str], conflicts: &[&str], after: &[&str]) -> Module {
+        let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect();
+        Module {
+            id: id.to_string(),
+            source_path: PathBuf::from("/data/adb/modules").join(id),
+            prop: ModuleProp::default(),
+            rules: ModuleRules::default(),
+            rule_origins: RuleOrigins::default(),
+            priority: 0,
+            dependencies: Dependencies {
+                requires: ids(requires),
+                conflicts: ids(conflicts),
+                after: ids(after),
+            },
+            exclusion: None,
+            masked: BTreeSet::new(),
+        }
+    }
+
+    fn resolve(modules: Vec<Module>) -> Vec<(String, Option<String>)> {
+        resolve_dependencies(modules)
+            .into_iter()
+            .map(|m| (m.id, m.exclusion))
+            .collect()
+    }
+
+    fn order(modules: Vec<Module>) -> Vec<String> {
+        resolve_dependencies(modules)
+            .into_iter()
+            .map(|m| m.id)
+            .collect()
+    }
+
+    #[test]
+    fn conflicts_drop_the_upper_module() {
+        let resolved = resolve(vec![
+            module("a", &[], &["b"], &[]),
+            module("b", &[], &["a"], &[]),
+            module("c", &[], &["a"], &[]),
+        ]);
+
+        assert_eq!(
+            resolved,
+            [
+                ("b".to_string(), None),
+                ("c".to_string(), None),
+                (
+                    "a".to_string(),
+                    Some("conflicts with active module 'b'".to_string())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn conflict_with_a_module_missing_requirements_is_ignored() {
+        let resolved = resolve(vec![
+            module("a", &[], &["b"], &[]),
+            module("b", &["x"], &[], &[]),
+        ]);
+
+        assert_eq!(
+            resolved,
+            [
+                ("a".to_string(), None),
+                (
+                    "b".to_string(),
+                    Some("requires module 'x' which is not active".to_string())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_requirements_cascade() {
+        let resolved = resolve(vec![
+            module("a", &["b"], &[], &[]),
+            module("b", &["c"], &[], &[]),
+            module("c", &["x"], &[], &[]),
+            module("d", &[], &[], &[]),
+        ]);
+
+        assert_eq!(resolved[0], ("d".to_string(), None));
+        for (id, exclusion) in &resolved[1..] {
+            assert!(exclusion.is_some(), "{} should be excluded", id);
+        }
+    }
+
+    #[test]
+    fn requirement_dropped_by_conflict_excludes_dependents() {
+        let resolved = resolve(vec![
+            module("a", &[], &["b"], &[]),
+            module("b", &[], &[], &[]),
+            module("c", &["a"], &[], &[]),
+        ]);
+
+        assert_eq!(
+            resolved,
+            [
+                ("b".to_string(), None),
+                (
+                    "a".to_string(),
+                    Some("conflicts with active module 'b'".to_string())
+                ),
+                (
+                    "c".to_string(),
+                    Some("requires module 'a' which is not active".to_string())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn requirements_and_after_are_layered_below() {
+        assert_eq!(
+            order(vec![
+                module("a", &[], &[], &[]),
+                module("b", &["a"], &[], &[]),
+                module("c", &[], &[], &[]),
+            ]),
+            ["b", "a", "c"]
+        );
+        assert_eq!(
+            order(vec![
+                module("a", &[], &[], &[]),
+                module("b", &[], &[], &[]),
+                module("c", &[], &[], &["a"]),
+            ]),
+            ["b", "c", "a"]
+        );
+    }
+
+    #[test]
+    fn toposort_keeps_priority_order_of_unrelated_modules() {
+        assert_eq!(
+            order(vec![
+                module("a", &[], &[], &[]),
+                module("b", &[], &[], &[]),
+                module("c", &[], &[], &[]),
+            ]),
+            ["a", "b", "c"]
+        );
+        assert_eq!(
+            order(vec![
+                module("a", &[], &[], &[]),
+                module("b", &[], &[], &[]),
+                module("c", &[], &[], &["b"]),
+                module("d", &[], &[], &["c"]),
+            ]),
+            ["a", "d", "c", "b"]
+        );
+    }
+
+    #[test]
+    fn ordering_cycle_falls_back_to_priority_order() {
+        // Modules outside the cycle are placed first, then the cycle is
+        // broken at its topmost module.
+        let resolved = resolve(vec![
+            module("a", &[], &[], &["b"]),
+            module("b", &[], &[], &["a"]),
+            module("c", &[], &[], &[]),
+        ]);
+
+        assert_eq!(
+            resolved,
+            [
+                ("c".to_string(), None),
+                ("a".to_string(), None),
+                ("b".to_string(), None),
+            ]
+        );
+    }
+}
@@ -7,8 +7,8 @@ use crate::{
     core::{
         inventory,
         inventory::model as modules,
-        ops::{executor, planner, sync},
-        state, storage,
+        ops::{executor, manifest::ImageManifest, planner, sync},
+        recovery, state, storage,
         storage::StorageHandle,
     },
 };
@@ -93,10 +93,9 @@ impl MountController<StorageReady> {
             modules.len()
         );
 
-        sync::perform_sync(&modules, &self.state.handle.mount_point)?;
-
-        if self.state.handle.mode == "erofs_staging" {
-            let needs_magic = modules.iter().any(|m| {
+        let erofs = self.state.handle.mode == "erofs_staging";
+        let needs_magic = erofs
+            && modules.iter().any(|m| {
                 m.rules.default_mode == inventory::MountMode::Magic
                     || m.rules
                         .paths
@@ -104,6 +103,26 @@ impl MountController<StorageReady> {
                         .any(|v| *v == inventory::MountMode::Magic)
             });
 
+        let image_manifest = if erofs {
+            match ImageManifest::build(&modules, &self.config, needs_magic) {
+                Ok(manifest) => Some(manifest),
+                Err(e) => {
+                    log::warn!("Failed to build EROFS image manifest: {:#}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        if image_manifest
+            .as_ref()
+            .is_some_and(|m| self.state.handle.image_is_current(m))
+        {
+            log::info!(">> Module Sync: No module changed since the EROFS image was packed.");
+        } else {
+            sync::perform_sync(&modules, &self.state.handle.mount_point, &self.config)?;
+
             if needs_magic {
                 let magic_ws = self.state.handle.mount_point.join("magic_workspace");
                 if !magic_ws.exists() {
@@ -112,7 +131,9 @@ impl MountController<StorageReady> {
             }
         }
 
-        self.state.handle.commit(self.config.disable_umount)?;
+        self.state
+            .handle
+            .commit(self.config.disable_umount, image_manifest.as_ref())?;
 
         Ok(MountController {
             config: self.config,
@@ -145,9 +166,34 @@ impl MountController<ModulesReady> {
 }
 
 impl MountController<Planned> {
-    pub fn execute(self) -> Result<MountController<Executed>> {
+    pub fn execute(mut self) -> Result<MountController<Executed>> {
         log::info!(">> Link Start! Executing mount plan...");
 
+        // The mount base is unmounted once the plan is executed, from here on
+        // the storage is only reachable through the retained bind.
+        match self.state.handle.retain(self.config.disable_umount) {
+            Ok(path) => self.state.handle.mount_point = path,
+            Err(e) => log::warn!(
+                "Failed to retain module storage, reload will be unavailable: {:#}",
+                e
+            ),
+        }
+
+        let mut active_modules: Vec<String> = self
+            .state
+            .plan
+            .overlay_module_ids
+            .iter()
+            .chain(&self.state.plan.magic_module_ids)
+            .cloned()
+            .collect();
+        active_modules.sort();
+        active_modules.dedup();
+
+        if let Err(e) = recovery::begin_boot(&active_modules) {
+            log::warn!("Failed to record boot attempt: {:#}", e);
+        }
+
         let result = executor::execute(&self.state.plan, &self.config, self.tempdir.clone())?;
 
         Ok(MountController {
@@ -184,9 +230,14 @@ impl MountController<Executed> {
         let state = state::RuntimeState::new(
             self.state.handle.mode,
             self.state.handle.mount_point,
+            self.state.handle.backing_image,
+            self.state.handle.loop_device,
             self.state.result.overlay_module_ids,
             self.state.result.magic_module_ids,
+            self.state.result.magic_scopes,
             active_mounts,
+            self.state.result.rollbacks,
+            self.state.result.mounts,
         );
 
         if let Err(e) = state.save() {
@@ -1,6 +1,7 @@
 pub mod inventory;
 pub mod manager;
 pub mod ops;
+pub mod recovery;
 pub mod state;
 pub mod storage;
 
@@ -1,12 +1,16 @@
-use std::{collections::HashSet, path::Path};
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+};
 
 use anyhow::Result;
 
 use crate::{
     conf::config,
-    core::ops::planner::MountPlan,
+    core::ops::planner::{MountPlan, OverlayOperation},
     defs,
     mount::{
+        journal::{self, MountKind, MountRecord, PhaseRollback},
         magic_mount,
         overlayfs::{self, utils::umount_dir},
         umount_mgr,
@@ -17,18 +21,22 @@ use crate::{
 pub struct ExecutionResult {
     pub overlay_module_ids: Vec<String>,
     pub magic_module_ids: Vec<String>,
+    pub magic_scopes: BTreeMap<String, Vec<PathBuf>>,
+    pub rollbacks: Vec<PhaseRollback>,
+    pub mounts: Vec<MountRecord>,
 }
 
-pub fn execute<P>(plan: &MountPlan, config: &config::Config, tempdir: P) -> Result<ExecutionResult>
-where
-    P: AsRef<Path>,
-{
-    let mut final_magic_ids: HashSet<String> = plan.magic_module_ids.iter().cloned().collect();
-    let mut final_overlay_ids: HashSet<String> = HashSet::new();
-
-    log::info!(">> Phase 1: OverlayFS Execution...");
+#[derive(Default)]
+pub struct Execution {
+    pub overlay_ids: HashSet<String>,
+    pub magic_ids: HashSet<String>,
+    pub magic_scopes: BTreeMap<String, Vec<PathBuf>>,
+    pub module_order: Vec<String>,
+    pub rollbacks: Vec<PhaseRollback>,
+}
 
-    for op in &plan.overlay_ops {
+impl Execution {
+    pub fn mount_overlay(&mut self, op: &OverlayOperation, config: &config::Config) {
         let involved_modules: Vec<String> = op
             .lowerdirs
             .iter()
@@ -58,6 +66,8 @@ where
             lowerdir_strings.len()
         );
 
+        let mark = journal::mark();
+
         match overlayfs::overlayfs::mount_overlay(
             &op.target,
             &lowerdir_strings,
@@ -66,9 +76,8 @@ where
             &config.mountsource,
         ) {
             Ok(_) => {
-                for id in involved_modules {
-                    final_overlay_ids.insert(id);
-                }
+                journal::claim_since(mark, &involved_modules);
+                self.overlay_ids.extend(involved_modules);
 
                 #[cfg(any(target_os = "linux", target_os = "android"))]
                 if !config.disable_umount
@@ -87,19 +96,107 @@ where
                     op.target,
                     e
                 );
-                for id in involved_modules {
-                    final_magic_ids.insert(id);
+                self.rollbacks.push(PhaseRollback {
+                    phase: format!("overlay:{}", op.target),
+                    reason: format!("{:#}", e),
+                    report: journal::rollback(mark),
+                });
+                self.magic_ids.extend(involved_modules);
+                for layer in &op.lowerdirs {
+                    match utils::split_module_path(layer) {
+                        Some((id, relative)) => {
+                            self.magic_scopes.entry(id).or_default().push(relative);
+                        }
+                        None => log::warn!(
+                            "Cannot locate module root of {}, skipping its fallback.",
+                            layer.display()
+                        ),
+                    }
                 }
             }
         }
     }
 
-    final_overlay_ids.retain(|id| !final_magic_ids.contains(id));
+    pub fn mount_magic(
+        &mut self,
+        magic_ws_path: &Path,
+        module_dir: &Path,
+        config: &config::Config,
+    ) {
+        self.magic_scopes
+            .retain(|id, _| self.magic_ids.contains(id));
+
+        let mark = journal::mark();
+        let result = (|| -> Result<()> {
+            if matches!(config.overlay_mode, config::OverlayMode::Erofs) {
+                if magic_ws_path.exists() {
+                    crate::sys::mount::mount_tmpfs(magic_ws_path, "magic_ws")?;
+                    journal::record(MountKind::Workspace, magic_ws_path);
+                } else {
+                    log::error!("Magic Mount anchor missing in EROFS image!");
+                }
+            } else if !magic_ws_path.exists() {
+                std::fs::create_dir_all(magic_ws_path)?;
+            }
 
-    let mut magic_queue: Vec<String> = final_magic_ids.iter().cloned().collect();
-    magic_queue.sort();
+            magic_mount::magic_mount(
+                magic_ws_path,
+                module_dir,
+                &config.mountsource,
+                &config.magic_partitions(),
+                &self.magic_scopes,
+                &self.module_order,
+                !config.disable_umount,
+            )
+        })();
+
+        match result {
+            Ok(()) => {
+                let mut ids: Vec<String> = self.magic_ids.iter().cloned().collect();
+                ids.sort();
+                journal::claim_since(mark, &ids);
+            }
+            Err(e) => {
+                log::error!("Magic Mount critical failure: {:#}", e);
+                let report = journal::rollback(mark);
+                log::warn!(
+                    "Rolled back {} magic mount(s), {} failed to revert",
+                    report.unmounted.len(),
+                    report.failed.len()
+                );
+                self.rollbacks.push(PhaseRollback {
+                    phase: "magic".to_string(),
+                    reason: format!("{:#}", e),
+                    report,
+                });
+                self.magic_ids.clear();
+                self.magic_scopes.clear();
+            }
+        }
+    }
+}
+
+pub fn execute<P>(plan: &MountPlan, config: &config::Config, tempdir: P) -> Result<ExecutionResult>
+where
+    P: AsRef<Path>,
+{
+    let mut execution = Execution {
+        magic_ids: plan.magic_module_ids.iter().cloned().collect(),
+        magic_scopes: plan.magic_scopes.clone(),
+        module_order: plan.module_order.clone(),
+        ..Default::default()
+    };
+
+    log::info!(">> Phase 1: OverlayFS Execution...");
 
-    if !magic_queue.is_empty() {
+    for op in &plan.overlay_ops {
+        execution.mount_overlay(op, config);
+    }
+
+    let magic_ids = &execution.magic_ids;
+    execution.overlay_ids.retain(|id| !magic_ids.contains(id));
+
+    if !execution.magic_ids.is_empty() {
         let magic_ws_path = tempdir.as_ref().join("magic_workspace");
         let _ = umount_mgr::TMPFS.set(magic_ws_path.to_string_lossy().to_string());
 
@@ -108,30 +205,7 @@ where
             magic_ws_path.display()
         );
 
-        if matches!(config.overlay_mode, config::OverlayMode::Erofs) {
-            if magic_ws_path.exists() {
-                crate::sys::mount::mount_tmpfs(&magic_ws_path, "magic_ws")?;
-            } else {
-                log::error!("Magic Mount anchor missing in EROFS image!");
-            }
-        } else if !magic_ws_path.exists() {
-            std::fs::create_dir_all(&magic_ws_path)?;
-        }
-
-        let module_dir = tempdir.as_ref();
-        let magic_need_ids: HashSet<String> = magic_queue.iter().cloned().collect();
-
-        if let Err(e) = magic_mount::magic_mount(
-            &magic_ws_path,
-            module_dir,
-            &config.mountsource,
-            &config.partitions,
-            magic_need_ids,
-            !config.disable_umount,
-        ) {
-            log::error!("Magic Mount critical failure: {:#}", e);
-            final_magic_ids.clear();
-        }
+        execution.mount_magic(&magic_ws_path, tempdir.as_ref(), config);
     }
 
     if let Err(e) = umount_dir(tempdir.as_ref()) {
@@ -152,8 +226,8 @@ where
         }
     }
 
-    let mut result_overlay: Vec<String> = final_overlay_ids.into_iter().collect();
-    let mut result_magic: Vec<String> = final_magic_ids.into_iter().collect();
+    let mut result_overlay: Vec<String> = execution.overlay_ids.into_iter().collect();
+    let mut result_magic: Vec<String> = execution.magic_ids.into_iter().collect();
 
     result_overlay.sort();
     result_magic.sort();
@@ -161,5 +235,8 @@ where
     Ok(ExecutionResult {
         overlay_module_ids: result_overlay,
         magic_module_ids: result_magic,
+        magic_scopes: execution.magic_scopes,
+        rollbacks: execution.rollbacks,
+        mounts: journal::entries(),
     })
 }
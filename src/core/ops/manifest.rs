@@ -0,0 +1,247 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{self, File},
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+use crate::{conf::config::Config, core::inventory::Module, defs, utils};
+
+/// Decides which entries of a module are copied into storage: the partition
+/// directories that can be mounted and `module.prop`, plus `sync.include`,
+/// minus `sync.exclude`. `module.prop` is never excluded, since a synced
+/// copy without it is not recognised as a module.
+pub struct SyncFilter<'a> {
+    partitions: &'a [String],
+    include: &'a [String],
+    exclude: &'a [String],
+}
+
+impl<'a> SyncFilter<'a> {
+    pub fn new(config: &'a Config) -> Self {
+        Self {
+            partitions: &config.partitions,
+            include: &config.sync.include,
+            exclude: &config.sync.exclude,
+        }
+    }
+
+    fn is_mountable(&self, relative: &str) -> bool {
+        if relative == "module.prop" {
+            return true;
+        }
+        let top = relative.split('/').next().unwrap_or_default();
+        defs::BUILTIN_PARTITIONS.contains(&top) || self.partitions.iter().any(|p| p == top)
+    }
+
+    fn is_excluded(&self, relative: &str) -> bool {
+        relative != "module.prop"
+            && self
+                .exclude
+                .iter()
+                .any(|pattern| utils::glob_match_prefix(pattern, relative))
+    }
+
+    pub fn matches(&self, relative: &str) -> bool {
+        !self.is_excluded(relative)
+            && (self.is_mountable(relative)
+                || self
+                    .include
+                    .iter()
+                    .any(|pattern| utils::glob_match_prefix(pattern, relative)))
+    }
+
+    /// Total size of the regular files of `module` that are left out.
+    pub fn skipped_bytes(&self, module: &Module) -> u64 {
+        let root = &module.source_path;
+        WalkDir::new(root)
+            .min_depth(1)
+            .into_iter()
+            .flatten()
+            .filter(|e| e.file_type().is_file())
+            .filter(|e| {
+                let rel = e.path().strip_prefix(root).unwrap_or(e.path());
+                !self.matches(&rel.to_string_lossy())
+            })
+            .filter_map(|e| e.metadata().ok())
+            .map(|m| m.len())
+            .sum()
+    }
+
+    /// Whether directory `relative` can contain entries that match.
+    pub fn descend(&self, relative: &str) -> bool {
+        self.matches(relative)
+            || (!self.is_excluded(relative)
+                && self
+                    .include
+                    .iter()
+                    .any(|pattern| utils::glob_may_match_below(pattern, relative)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub path: String,
+    pub size: u64,
+    pub mtime: i64,
+    pub mtime_nsec: i64,
+    pub mode: u32,
+    /// Attribute name to hex encoded value.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub xattrs: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+}
+
+/// Description of a module source as it was synced, kept inside the synced
+/// copy so it is replaced together with it.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    pub entries: Vec<ManifestEntry>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub masked: BTreeSet<String>,
+}
+
+fn hash_file(path: &Path) -> Result<String> {
+    Ok(utils::sha256_reader_hex(&mut File::open(path)?)?)
+}
+
+impl Manifest {
+    /// Walks the entries of `module` that pass `filter`. Directory mtimes are
+    /// left out since the entries below them already cover their content;
+    /// `hash` adds a content hash for regular files.
+    pub fn build(module: &Module, filter: &SyncFilter, hash: bool) -> Result<Self> {
+        let root = &module.source_path;
+        let relative = |path: &Path| {
+            path.strip_prefix(root)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .to_string()
+        };
+        let mut entries = Vec::new();
+
+        for entry in WalkDir::new(root)
+            .min_depth(1)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|e| !e.file_type().is_dir() || filter.descend(&relative(e.path())))
+        {
+            let entry = entry.with_context(|| format!("failed to walk {}", root.display()))?;
+            let path = entry.path();
+            let rel = relative(path);
+            if !entry.file_type().is_dir() && !filter.matches(&rel) {
+                continue;
+            }
+
+            let metadata = entry
+                .metadata()
+                .with_context(|| format!("failed to stat {}", path.display()))?;
+            let is_dir = metadata.is_dir();
+
+            entries.push(ManifestEntry {
+                path: rel,
+                size: if is_dir { 0 } else { metadata.size() },
+                mtime: if is_dir { 0 } else { metadata.mtime() },
+                mtime_nsec: if is_dir { 0 } else { metadata.mtime_nsec() },
+                mode: metadata.mode(),
+                xattrs: utils::lgetxattrs(path)
+                    .into_iter()
+                    .map(|(name, value)| (name, utils::encode_hex(&value)))
+                    .collect(),
+                hash: if hash && metadata.is_file() {
+                    Some(
+                        hash_file(path)
+                            .with_context(|| format!("failed to hash {}", path.display()))?,
+                    )
+                } else {
+                    None
+                },
+            });
+        }
+
+        Ok(Self {
+            entries,
+            masked: module.masked.clone(),
+        })
+    }
+
+    /// Manifest of the synced copy in `dir`, if it has a readable one.
+    pub fn load(dir: &Path) -> Option<Self> {
+        let content = fs::read(dir.join(defs::MANIFEST_FILE_NAME)).ok()?;
+        serde_json::from_slice(&content).ok()
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        let content = serde_json::to_vec(self).context("failed to serialize manifest")?;
+        fs::write(dir.join(defs::MANIFEST_FILE_NAME), content).context("failed to write manifest")
+    }
+}
+
+/// Manifests of every module packed into an EROFS image, kept next to the
+/// image so an unchanged module set can reuse it instead of packing again.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ImageManifest {
+    pub modules: BTreeMap<String, Manifest>,
+    #[serde(default)]
+    pub magic_workspace: bool,
+}
+
+impl ImageManifest {
+    pub fn build(modules: &[Module], config: &Config, magic_workspace: bool) -> Result<Self> {
+        let filter = SyncFilter::new(config);
+        let modules = modules
+            .par_iter()
+            .map(|module| {
+                Manifest::build(module, &filter, config.sync.hash)
+                    .with_context(|| format!("failed to build manifest for {}", module.id))
+                    .map(|manifest| (module.id.clone(), manifest))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            modules,
+            magic_workspace,
+        })
+    }
+
+    pub fn path(image: &Path) -> PathBuf {
+        let mut path = image.as_os_str().to_owned();
+        path.push(".manifest.json");
+        PathBuf::from(path)
+    }
+
+    /// Manifest saved for `image`, if it has a readable one.
+    pub fn load(image: &Path) -> Option<Self> {
+        let content = fs::read(Self::path(image)).ok()?;
+        serde_json::from_slice(&content).ok()
+    }
+
+    pub fn save(&self, image: &Path) -> Result<()> {
+        let content = serde_json::to_vec(self).context("failed to serialize image manifest")?;
+        fs::write(Self::path(image), content).context("failed to write image manifest")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn module_prop_is_never_excluded() {
+        let mut config = Config::default();
+        config.sync.exclude = vec!["**".to_string(), "module.prop".to_string()];
+        let filter = SyncFilter::new(&config);
+
+        assert!(filter.matches("module.prop"));
+        assert!(!filter.matches("system"));
+        assert!(!filter.matches("system/bin/sh"));
+    }
+}
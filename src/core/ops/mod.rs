@@ -1,3 +1,6 @@
 pub mod executor;
+pub mod manifest;
 pub mod planner;
+pub mod reload;
 pub mod sync;
+pub mod teardown;
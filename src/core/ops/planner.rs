@@ -1,5 +1,5 @@
 use std::{
-    collections::{HashMap, HashSet, VecDeque},
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
     fs,
     path::{Path, PathBuf},
 };
@@ -11,29 +11,40 @@ use walkdir::WalkDir;
 
 use crate::{
     conf::config,
-    core::inventory::{Module, MountMode},
+    core::inventory::{Module, MountMode, RuleSource},
     defs, utils,
 };
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct OverlayOperation {
     pub partition_name: String,
     pub target: String,
     pub lowerdirs: Vec<PathBuf>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Serialize)]
 pub struct MountPlan {
     pub overlay_ops: Vec<OverlayOperation>,
     pub overlay_module_ids: Vec<String>,
     pub magic_module_ids: Vec<String>,
+    pub magic_scopes: BTreeMap<String, Vec<PathBuf>>,
+    pub split_targets: Vec<String>,
+    /// Module ids from the topmost layer down, the order used for overlay
+    /// lowerdirs and for merging magic mount nodes.
+    pub module_order: Vec<String>,
+    /// Modules left out because of their dependencies, with the reason.
+    pub excluded_modules: BTreeMap<String, String>,
 }
 
 #[derive(Debug, Clone, Serialize)]
 pub struct ConflictEntry {
     pub partition: String,
     pub relative_path: String,
+    /// Topmost layer first.
     pub contending_modules: Vec<String>,
+    /// Whether a `conflict_resolution` entry pins one of the contenders.
+    pub resolved: bool,
+    pub winner: String,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -55,8 +66,30 @@ pub struct AnalysisReport {
     pub diagnostics: Vec<DiagnosticIssue>,
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct PathProvider {
+    pub module: String,
+    pub source: PathBuf,
+    pub relative_path: String,
+    pub matched_rule: Option<String>,
+    pub mode: MountMode,
+    pub rule_source: RuleSource,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PathExplanation {
+    pub path: String,
+    pub resolved_path: String,
+    pub overlay_target: Option<String>,
+    pub partition: Option<String>,
+    pub layers: Vec<String>,
+    pub split_by_sensitive_partition: bool,
+    pub split_points: Vec<String>,
+    pub providers: Vec<PathProvider>,
+}
+
 impl MountPlan {
-    pub fn analyze(&self) -> AnalysisReport {
+    pub fn analyze(&self, config: &config::Config) -> AnalysisReport {
         let results: Vec<(Vec<ConflictEntry>, Vec<DiagnosticIssue>)> = self
             .overlay_ops
             .par_iter()
@@ -64,6 +97,7 @@ impl MountPlan {
                 let mut local_conflicts = Vec::new();
                 let mut local_diagnostics = Vec::new();
                 let mut file_map: HashMap<String, Vec<String>> = HashMap::new();
+                let mut layer_prefixes: HashMap<String, PathBuf> = HashMap::new();
 
                 if !Path::new(&op.target).exists() {
                     local_diagnostics.push(DiagnosticIssue {
@@ -80,6 +114,10 @@ impl MountPlan {
 
                     let module_id =
                         utils::extract_module_id(layer_path).unwrap_or_else(|| "UNKNOWN".into());
+                    let prefix = utils::split_module_path(layer_path)
+                        .map(|(_, relative)| relative)
+                        .unwrap_or_else(|| PathBuf::from(op.target.trim_start_matches('/')));
+                    layer_prefixes.insert(module_id.clone(), prefix);
 
                     for entry in WalkDir::new(layer_path).min_depth(1).into_iter().flatten() {
                         if entry.path_is_symlink()
@@ -111,9 +149,17 @@ impl MountPlan {
 
                 for (rel_path, modules) in file_map {
                     if modules.len() > 1 {
+                        let module_path = layer_prefixes[&modules[0]].join(&rel_path);
+                        let pinned = config
+                            .conflict_winner(&module_path.to_string_lossy())
+                            .map(|(_, id)| id)
+                            .filter(|id| modules.iter().any(|m| m == id));
+
                         local_conflicts.push(ConflictEntry {
                             partition: op.partition_name.clone(),
                             relative_path: rel_path,
+                            resolved: pinned.is_some(),
+                            winner: pinned.unwrap_or(&modules[0]).to_string(),
                             contending_modules: modules,
                         });
                     }
@@ -124,6 +170,13 @@ impl MountPlan {
             .collect();
 
         let mut report = AnalysisReport::default();
+        for (id, reason) in &self.excluded_modules {
+            report.diagnostics.push(DiagnosticIssue {
+                level: DiagnosticLevel::Critical,
+                context: id.clone(),
+                message: format!("Module is not mounted: {}", reason),
+            });
+        }
         for (c, d) in results {
             report.conflicts.extend(c);
             report.diagnostics.extend(d);
@@ -137,12 +190,118 @@ impl MountPlan {
 
         report
     }
+
+    pub fn explain(&self, path: &Path, modules: &[Module], storage_root: &Path) -> PathExplanation {
+        let resolved = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+
+        let matched_op = self
+            .overlay_ops
+            .iter()
+            .filter(|op| resolved.starts_with(&op.target))
+            .max_by_key(|op| op.target.len());
+
+        let layers = matched_op
+            .map(|op| {
+                op.lowerdirs
+                    .iter()
+                    .map(|p| utils::extract_module_id(p).unwrap_or_else(|| "UNKNOWN".into()))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let split_points: Vec<String> = self
+            .split_targets
+            .iter()
+            .filter(|target| resolved.starts_with(target))
+            .cloned()
+            .collect();
+
+        let mut candidates: Vec<PathBuf> = Vec::new();
+        for rel in [path, resolved.as_path()]
+            .into_iter()
+            .filter_map(|p| p.strip_prefix("/").ok())
+        {
+            let mut variants = vec![rel.to_path_buf()];
+            if !rel.starts_with("system") {
+                variants.push(Path::new("system").join(rel));
+            }
+            for variant in variants {
+                if !candidates.contains(&variant) {
+                    candidates.push(variant);
+                }
+            }
+        }
+
+        let mut providers = Vec::new();
+        for module in modules.iter().filter(|m| m.exclusion.is_none()) {
+            let content_path = module.content_path(storage_root);
+
+            for rel in &candidates {
+                let source = content_path.join(rel);
+                if source.symlink_metadata().is_err() {
+                    continue;
+                }
+
+                let relative_path = rel.to_string_lossy().to_string();
+
+                providers.push(PathProvider {
+                    module: module.id.clone(),
+                    source,
+                    mode: module.rules.get_mode(&relative_path),
+                    matched_rule: module
+                        .rules
+                        .matching_rule(&relative_path)
+                        .map(|(pattern, _)| pattern.to_string()),
+                    rule_source: module.rule_origins.source_of(&module.rules, &relative_path),
+                    relative_path,
+                });
+                break;
+            }
+        }
+
+        PathExplanation {
+            path: path.display().to_string(),
+            resolved_path: resolved.display().to_string(),
+            overlay_target: matched_op.map(|op| op.target.clone()),
+            partition: matched_op.map(|op| op.partition_name.clone()),
+            layers,
+            split_by_sensitive_partition: !split_points.is_empty(),
+            split_points,
+            providers,
+        }
+    }
 }
 
 struct ProcessingItem {
     module_source: PathBuf,
     system_target: PathBuf,
     partition_label: String,
+    relative_path: String,
+}
+
+fn resolve_target(system_target: &Path) -> PathBuf {
+    let resolved_target = match fs::read_link(system_target) {
+        Ok(target) => {
+            if target.is_absolute() {
+                target
+            } else {
+                system_target
+                    .parent()
+                    .unwrap_or(Path::new("/"))
+                    .join(target)
+            }
+        }
+        Err(_) => system_target.to_path_buf(),
+    };
+
+    if resolved_target.exists() {
+        match resolved_target.canonicalize() {
+            Ok(p) => p,
+            Err(_) => resolved_target,
+        }
+    } else {
+        resolved_target
+    }
 }
 
 pub fn generate(
@@ -156,18 +315,26 @@ pub fn generate(
 
     let mut overlay_ids = HashSet::new();
     let mut magic_ids = HashSet::new();
+    let mut split_targets = HashSet::new();
 
     let sensitive_partitions: HashSet<&str> = defs::SENSITIVE_PARTITIONS.iter().cloned().collect();
 
+    // `modules` come in layer order from `inventory::scan`, topmost first.
     for module in modules {
-        let mut content_path = storage_root.join(&module.id);
-        if !content_path.exists() {
-            content_path = module.source_path.clone();
+        if let Some(reason) = &module.exclusion {
+            plan.excluded_modules
+                .insert(module.id.clone(), reason.clone());
+            continue;
         }
+
+        plan.module_order.push(module.id.clone());
+        let content_path = module.content_path(storage_root);
         if !content_path.exists() {
             continue;
         }
 
+        let mut queue = VecDeque::new();
+
         if let Ok(entries) = fs::read_dir(&content_path) {
             for entry in entries.flatten() {
                 let path = entry.path();
@@ -183,89 +350,106 @@ pub fn generate(
                     continue;
                 }
 
-                let mode = module.rules.get_mode(&dir_name);
-                if matches!(mode, MountMode::Magic) {
-                    magic_ids.insert(module.id.clone());
-                    continue;
+                queue.push_back(ProcessingItem {
+                    module_source: path,
+                    system_target: PathBuf::from("/").join(&dir_name),
+                    partition_label: dir_name.clone(),
+                    relative_path: dir_name,
+                });
+            }
+        }
+
+        while let Some(item) = queue.pop_front() {
+            let ProcessingItem {
+                module_source,
+                system_target,
+                partition_label,
+                relative_path,
+            } = item;
+
+            if module.rules.has_rules_below(&relative_path) {
+                // A nested rule picks a different mode, so this directory cannot be
+                // handled as one unit. Subdirectories are queued to be resolved on their
+                // own; files sitting directly in it go to magic mount, since overlayfs
+                // can only be stacked on directories.
+                if let Ok(sub_entries) = fs::read_dir(&module_source) {
+                    for sub_entry in sub_entries.flatten() {
+                        let sub_name = sub_entry.file_name().to_string_lossy().to_string();
+                        let sub_relative = format!("{relative_path}/{sub_name}");
+
+                        if sub_entry.file_type().is_ok_and(|ft| ft.is_dir()) {
+                            queue.push_back(ProcessingItem {
+                                module_source: sub_entry.path(),
+                                system_target: system_target.join(&sub_name),
+                                partition_label: partition_label.clone(),
+                                relative_path: sub_relative,
+                            });
+                        } else if !matches!(module.rules.get_mode(&sub_relative), MountMode::Ignore)
+                        {
+                            magic_ids.insert(module.id.clone());
+                            plan.magic_scopes
+                                .entry(module.id.clone())
+                                .or_default()
+                                .push(PathBuf::from(sub_relative));
+                        }
+                    }
                 }
-                if matches!(mode, MountMode::Ignore) {
+                continue;
+            }
+
+            match module.rules.get_mode(&relative_path) {
+                MountMode::Ignore => continue,
+                MountMode::Magic => {
+                    magic_ids.insert(module.id.clone());
+                    plan.magic_scopes
+                        .entry(module.id.clone())
+                        .or_default()
+                        .push(PathBuf::from(relative_path));
                     continue;
                 }
+                MountMode::Overlay => {}
+            }
 
-                overlay_ids.insert(module.id.clone());
+            if !system_target.exists() {
+                continue;
+            }
 
-                let mut queue = VecDeque::new();
-                queue.push_back(ProcessingItem {
-                    module_source: path.clone(),
-                    system_target: PathBuf::from("/").join(&dir_name),
-                    partition_label: dir_name.clone(),
-                });
+            overlay_ids.insert(module.id.clone());
 
-                while let Some(item) = queue.pop_front() {
-                    let ProcessingItem {
-                        module_source,
-                        system_target,
-                        partition_label,
-                    } = item;
+            let canonical_target = resolve_target(&system_target);
 
-                    if !system_target.exists() {
-                        continue;
-                    }
+            let target_name = canonical_target
+                .file_name()
+                .map(|s| s.to_string_lossy())
+                .unwrap_or_default();
 
-                    let resolved_target = match fs::read_link(&system_target) {
-                        Ok(target) => {
-                            if target.is_absolute() {
-                                target
-                            } else {
-                                system_target
-                                    .parent()
-                                    .unwrap_or(Path::new("/"))
-                                    .join(target)
-                            }
-                        }
-                        Err(_) => system_target.clone(),
-                    };
+            let should_split =
+                sensitive_partitions.contains(target_name.as_ref()) || target_name == "system";
 
-                    let canonical_target = if resolved_target.exists() {
-                        match resolved_target.canonicalize() {
-                            Ok(p) => p,
-                            Err(_) => resolved_target,
-                        }
-                    } else {
-                        resolved_target
-                    };
-
-                    let target_name = canonical_target
-                        .file_name()
-                        .map(|s| s.to_string_lossy())
-                        .unwrap_or_default();
-
-                    let should_split = sensitive_partitions.contains(target_name.as_ref())
-                        || target_name == "system";
-
-                    if should_split {
-                        if let Ok(sub_entries) = fs::read_dir(&module_source) {
-                            for sub_entry in sub_entries.flatten() {
-                                let sub_path = sub_entry.path();
-                                if !sub_path.is_dir() {
-                                    continue;
-                                }
-                                let sub_name = sub_entry.file_name();
-
-                                queue.push_back(ProcessingItem {
-                                    module_source: sub_path,
-                                    system_target: canonical_target.join(sub_name),
-                                    partition_label: partition_label.clone(),
-                                });
-                            }
+            if should_split {
+                split_targets.insert(canonical_target.to_string_lossy().to_string());
+
+                if let Ok(sub_entries) = fs::read_dir(&module_source) {
+                    for sub_entry in sub_entries.flatten() {
+                        let sub_path = sub_entry.path();
+                        if !sub_path.is_dir() {
+                            continue;
                         }
-                    } else {
-                        overlay_groups
-                            .entry(canonical_target)
-                            .or_default()
-                            .push(module_source);
+                        let sub_name = sub_entry.file_name().to_string_lossy().to_string();
+
+                        queue.push_back(ProcessingItem {
+                            module_source: sub_path,
+                            system_target: canonical_target.join(&sub_name),
+                            partition_label: partition_label.clone(),
+                            relative_path: format!("{relative_path}/{sub_name}"),
+                        });
                     }
                 }
+            } else {
+                overlay_groups
+                    .entry(canonical_target)
+                    .or_default()
+                    .push(module_source);
             }
         }
     }
@@ -290,10 +474,145 @@ pub fn generate(
         });
     }
 
+    // Rule splits can leave one module overlaying a directory that another module
+    // overlays below, so parents have to be mounted before their children.
+    plan.overlay_ops.sort_by(|a, b| a.target.cmp(&b.target));
+
     plan.overlay_module_ids = overlay_ids.into_iter().collect();
     plan.magic_module_ids = magic_ids.into_iter().collect();
+    plan.split_targets = split_targets.into_iter().collect();
     plan.overlay_module_ids.sort();
     plan.magic_module_ids.sort();
+    plan.split_targets.sort();
 
     Ok(plan)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::inventory::{Dependencies, RuleOrigins},
+        mount::{magic_mount::utils::collect_module_files, node::Node},
+    };
+
+    fn module(storage: &Path, id: &str, rules: config::ModuleRules) -> Module {
+        Module {
+            id: id.to_string(),
+            source_path: storage.join(id),
+            prop: Default::default(),
+            rules,
+            rule_origins: RuleOrigins::default(),
+            priority: 0,
+            dependencies: Dependencies::default(),
+            exclusion: None,
+            masked: Default::default(),
+        }
+    }
+
+    fn touch(path: &Path) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "").unwrap();
+    }
+
+    fn child<'a>(node: &'a Node, path: &str) -> Option<&'a Node> {
+        path.split('/')
+            .try_fold(node, |node, name| node.children.get(name))
+    }
+
+    #[test]
+    fn top_level_partition_rule_is_magic_mounted() {
+        let storage = tempfile::tempdir().unwrap();
+        touch(&storage.path().join("m/vendor/lib/libfoo.so"));
+
+        let mut config = config::Config::default();
+        config
+            .partition_rules
+            .insert("vendor".to_string(), MountMode::Magic);
+        let rules = config::ModuleRules {
+            paths: config.partition_rule_patterns().into_iter().collect(),
+            ..Default::default()
+        };
+        let modules = [module(storage.path(), "m", rules)];
+
+        let plan = generate(&config, &modules, storage.path()).unwrap();
+
+        assert!(plan.overlay_ops.is_empty());
+        assert_eq!(plan.magic_scopes["m"], [PathBuf::from("vendor")]);
+        assert!(config.magic_partitions().contains(&"vendor".to_string()));
+
+        let root = collect_module_files(
+            storage.path(),
+            &config.magic_partitions(),
+            &plan.magic_scopes,
+            &plan.module_order,
+        )
+        .unwrap()
+        .expect("vendor files are collected");
+
+        // Whether vendor ends up below /system or at the root depends on the
+        // layout of the host.
+        assert!(
+            child(&root, "system/vendor/lib/libfoo.so")
+                .or_else(|| child(&root, "vendor/lib/libfoo.so"))
+                .is_some()
+        );
+        assert!(child(&root, "system/lib").is_none());
+    }
+
+    #[test]
+    fn nested_rule_splits_its_parent_directory() {
+        // Overlay targets have to exist, so the module patches a scratch
+        // directory below /tmp through a `tmp` partition.
+        let target = tempfile::tempdir_in("/tmp").unwrap();
+        let name = target.path().file_name().unwrap().to_string_lossy();
+        let base = format!("tmp/{}", name);
+        fs::create_dir(target.path().join("app")).unwrap();
+        fs::create_dir(target.path().join("bin")).unwrap();
+
+        let storage = tempfile::tempdir().unwrap();
+        let content = storage.path().join("m").join(&base);
+        touch(&content.join("app/Foo/foo.apk"));
+        touch(&content.join("bin/tool"));
+        touch(&content.join("top.conf"));
+        touch(&content.join("skip.conf"));
+
+        let config = config::Config {
+            partitions: vec!["tmp".to_string()],
+            ..Default::default()
+        };
+        let rules = config::ModuleRules {
+            paths: HashMap::from([
+                (format!("{}/bin", base), MountMode::Magic),
+                (format!("{}/skip.conf", base), MountMode::Ignore),
+            ]),
+            ..Default::default()
+        };
+        let modules = [module(storage.path(), "m", rules)];
+
+        let plan = generate(&config, &modules, storage.path()).unwrap();
+
+        let mut scopes = plan.magic_scopes["m"].clone();
+        scopes.sort();
+        assert_eq!(
+            scopes,
+            [
+                PathBuf::from(format!("{}/bin", base)),
+                PathBuf::from(format!("{}/top.conf", base)),
+            ]
+        );
+
+        let targets: Vec<(&str, &[PathBuf])> = plan
+            .overlay_ops
+            .iter()
+            .map(|op| (op.target.as_str(), op.lowerdirs.as_slice()))
+            .collect();
+        let app = target.path().canonicalize().unwrap().join("app");
+        assert_eq!(
+            targets,
+            [(app.to_str().unwrap(), &[content.join("app")][..])]
+        );
+        assert_eq!(plan.overlay_module_ids, ["m"]);
+        assert_eq!(plan.magic_module_ids, ["m"]);
+    }
+}
@@ -0,0 +1,278 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, ensure};
+use serde::Serialize;
+
+use crate::{
+    conf::config::Config,
+    core::{
+        inventory,
+        ops::{
+            executor::Execution,
+            planner::{self, OverlayOperation},
+            sync,
+        },
+        state::RuntimeState,
+    },
+    mount::{
+        journal::{self, MountKind, MountRecord, PhaseRollback, UnmountReport},
+        umount_mgr,
+    },
+    sys::mount::is_mounted,
+    utils,
+};
+
+#[derive(Debug, Serialize)]
+pub struct ReloadReport {
+    pub module: String,
+    pub synced: bool,
+    pub unmounted: UnmountReport,
+    pub overlay_targets: Vec<String>,
+    pub magic_remounted: bool,
+    pub rollbacks: Vec<PhaseRollback>,
+}
+
+fn is_magic_kind(kind: MountKind) -> bool {
+    matches!(
+        kind,
+        MountKind::MagicBind | MountKind::MagicTmpfs | MountKind::Workspace
+    )
+}
+
+/// Takes the overlay mounts of `module_id` out of `mounts`, together with
+/// every mount of any module below them, since detaching a mount drops the
+/// mounts nested under it as well.
+fn take_stale(mounts: &mut Vec<MountRecord>, module_id: &str) -> Vec<MountRecord> {
+    let detached: Vec<PathBuf> = mounts
+        .iter()
+        .filter(|r| !is_magic_kind(r.kind) && r.modules.iter().any(|id| id == module_id))
+        .map(|r| r.target.clone())
+        .collect();
+
+    let (stale, kept): (Vec<_>, Vec<_>) = std::mem::take(mounts)
+        .into_iter()
+        .partition(|r| detached.iter().any(|t| r.target.starts_with(t)));
+    *mounts = kept;
+
+    stale
+}
+
+/// Whether `op` has to be mounted again: it stacks `module_id`, or its target
+/// went away with the `stale` mounts.
+fn needs_remount(op: &OverlayOperation, module_id: &str, stale: &[MountRecord]) -> bool {
+    op.lowerdirs
+        .iter()
+        .filter_map(|p| utils::extract_module_id(p))
+        .any(|id| id == module_id)
+        || stale
+            .iter()
+            .filter(|r| !is_magic_kind(r.kind))
+            .any(|r| Path::new(&op.target).starts_with(&r.target))
+}
+
+pub fn reload(config: &Config, module_id: &str) -> Result<ReloadReport> {
+    let mut state = RuntimeState::load().context("Failed to load runtime state")?;
+
+    ensure!(
+        !state.mount_point.as_os_str().is_empty() && is_mounted(&state.mount_point),
+        "Module storage is not mounted, nothing to reload"
+    );
+    ensure!(
+        state.storage_mode != "erofs",
+        "EROFS storage is read-only, a reboot is required to apply module changes"
+    );
+
+    let modules = inventory::scan(&config.moduledir, config)?;
+    let module = modules
+        .iter()
+        .find(|m| m.id == module_id)
+        .with_context(|| format!("Module {} is not installed or not enabled", module_id))?;
+
+    log::info!(">> Reloading module {}...", module_id);
+
+    let synced = sync::sync_module(module, &state.mount_point, true, config)
+        .with_context(|| format!("Failed to sync module {}", module_id))?;
+
+    let plan = planner::generate(config, &modules, &state.mount_point)?;
+
+    let stale = take_stale(&mut state.mounts, module_id);
+    let nested_magic = stale.iter().any(|r| is_magic_kind(r.kind));
+    let mut unmounted = journal::unmount_records(&stale);
+    let mut removed = stale;
+
+    let mut execution = Execution {
+        module_order: plan.module_order.clone(),
+        ..Default::default()
+    };
+    let mut overlay_targets = Vec::new();
+
+    log::info!(">> Phase 1: Remounting OverlayFS targets...");
+
+    for op in plan
+        .overlay_ops
+        .iter()
+        .filter(|op| needs_remount(op, module_id, &removed))
+    {
+        execution.mount_overlay(op, config);
+        overlay_targets.push(op.target.clone());
+    }
+
+    let mut magic_scopes = state.magic_scopes.clone();
+    magic_scopes.remove(module_id);
+    if let Some(scopes) = plan.magic_scopes.get(module_id) {
+        magic_scopes.insert(module_id.to_string(), scopes.clone());
+    }
+    for (id, scopes) in std::mem::take(&mut execution.magic_scopes) {
+        magic_scopes.entry(id).or_default().extend(scopes);
+    }
+
+    let magic_remounted = nested_magic
+        || state.magic_modules.iter().any(|id| id == module_id)
+        || magic_scopes != state.magic_scopes;
+
+    if magic_remounted {
+        log::info!(">> Phase 2: Re-running Magic Mount...");
+
+        let (stale, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut state.mounts)
+            .into_iter()
+            .partition(|r| is_magic_kind(r.kind));
+        state.mounts = kept;
+
+        let report = journal::unmount_records(&stale);
+        unmounted.unmounted.extend(report.unmounted);
+        unmounted.failed.extend(report.failed);
+        removed.extend(stale);
+
+        execution.magic_ids = magic_scopes.keys().cloned().collect();
+        execution.magic_scopes = magic_scopes;
+        execution.mount_magic(
+            &state.mount_point.join("magic_workspace"),
+            &state.mount_point,
+            config,
+        );
+
+        state.magic_modules = execution.magic_ids.iter().cloned().collect();
+        state.magic_modules.sort();
+        state.magic_scopes = std::mem::take(&mut execution.magic_scopes);
+    }
+
+    state.overlay_modules.retain(|id| id != module_id);
+    for id in &execution.overlay_ids {
+        if !state.overlay_modules.contains(id) {
+            state.overlay_modules.push(id.clone());
+        }
+    }
+    let magic_modules = &state.magic_modules;
+    state
+        .overlay_modules
+        .retain(|id| !magic_modules.contains(id));
+    state.overlay_modules.sort();
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    if !config.disable_umount
+        && let Err(e) = umount_mgr::commit()
+    {
+        log::warn!("try_umount commit failed: {}", e);
+    }
+
+    state.mounts.extend(
+        removed
+            .into_iter()
+            .filter(|r| unmounted.failed.iter().any(|f| f.target == r.target)),
+    );
+    state.mounts.extend(journal::entries());
+    state.rollbacks.extend(execution.rollbacks.iter().cloned());
+    state.save().context("Failed to save runtime state")?;
+
+    Ok(ReloadReport {
+        module: module_id.to_string(),
+        synced,
+        unmounted,
+        overlay_targets,
+        magic_remounted,
+        rollbacks: execution.rollbacks,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(kind: MountKind, target: &str, module: &str) -> MountRecord {
+        MountRecord {
+            kind,
+            target: PathBuf::from(target),
+            modules: vec![module.to_string()],
+        }
+    }
+
+    fn op(target: &str, lowerdir: &str) -> OverlayOperation {
+        OverlayOperation {
+            partition_name: "system".to_string(),
+            target: target.to_string(),
+            lowerdirs: vec![PathBuf::from(lowerdir)],
+        }
+    }
+
+    #[test]
+    fn nested_mounts_of_other_modules_are_remounted() {
+        let mut mounts = vec![
+            record(MountKind::Overlay, "/system/app", "a"),
+            record(MountKind::Overlay, "/system/app/Foo", "b"),
+            record(MountKind::MagicBind, "/system/app/Bar/bar.apk", "c"),
+            record(MountKind::Overlay, "/system/apps", "b"),
+            record(MountKind::Overlay, "/vendor/lib", "b"),
+        ];
+
+        let stale = take_stale(&mut mounts, "a");
+
+        let stale_targets: Vec<_> = stale.iter().map(|r| r.target.clone()).collect();
+        assert_eq!(
+            stale_targets,
+            [
+                PathBuf::from("/system/app"),
+                PathBuf::from("/system/app/Foo"),
+                PathBuf::from("/system/app/Bar/bar.apk"),
+            ]
+        );
+        assert_eq!(mounts.len(), 2);
+        assert!(stale.iter().any(|r| is_magic_kind(r.kind)));
+
+        assert!(needs_remount(
+            &op("/system/app", "/storage/a/system/app"),
+            "a",
+            &stale
+        ));
+        assert!(needs_remount(
+            &op("/system/app/Foo", "/storage/b/system/app/Foo"),
+            "a",
+            &stale
+        ));
+        assert!(!needs_remount(
+            &op("/system/apps", "/storage/b/system/apps"),
+            "a",
+            &stale
+        ));
+        assert!(!needs_remount(
+            &op("/vendor/lib", "/storage/b/vendor/lib"),
+            "a",
+            &stale
+        ));
+    }
+
+    #[test]
+    fn magic_mounts_of_the_module_are_kept_for_the_magic_phase() {
+        let mut mounts = vec![
+            record(MountKind::MagicBind, "/system/bin/tool", "a"),
+            record(MountKind::Overlay, "/system/etc", "b"),
+        ];
+
+        let stale = take_stale(&mut mounts, "a");
+
+        assert!(stale.is_empty());
+        assert_eq!(mounts.len(), 2);
+    }
+}
@@ -1,79 +1,226 @@
-use std::{collections::HashSet, fs, path::Path};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use rayon::prelude::*;
 use walkdir::WalkDir;
 
-use crate::{core::inventory::Module, defs, utils};
+use crate::{
+    conf::config::Config,
+    core::{
+        inventory::Module,
+        ops::manifest::{Manifest, ManifestEntry, SyncFilter},
+    },
+    defs, utils,
+};
 
-pub fn perform_sync(modules: &[Module], target_base: &Path) -> Result<()> {
+pub fn perform_sync(modules: &[Module], target_base: &Path, config: &Config) -> Result<()> {
     log::info!("Starting smart module sync to {}", target_base.display());
 
     prune_orphaned_modules(modules, target_base)?;
 
     modules.par_iter().for_each(|module| {
-        let dst = target_base.join(&module.id);
-        let dst_backup = target_base.join(format!(".backup_{}", module.id));
+        if let Err(e) = sync_module(module, target_base, false, config) {
+            log::error!("Failed to sync module {}: {:#}", module.id, e);
+        }
+    });
+
+    Ok(())
+}
 
-        let has_content = defs::BUILTIN_PARTITIONS.iter().any(|p| {
+pub fn sync_module(
+    module: &Module,
+    target_base: &Path,
+    force: bool,
+    config: &Config,
+) -> Result<bool> {
+    let dst = target_base.join(&module.id);
+    let dst_backup = target_base.join(format!(".backup_{}", module.id));
+
+    let has_content = defs::BUILTIN_PARTITIONS
+        .iter()
+        .copied()
+        .chain(config.partitions.iter().map(String::as_str))
+        .any(|p| {
             let part_path = module.source_path.join(p);
 
             part_path.exists() && has_files_recursive(&part_path)
         });
 
-        if has_content && should_sync(&module.source_path, &dst) {
-            log::info!("Syncing module: {} (Updated/New)", module.id);
+    if !has_content {
+        log::debug!("Skipping module: {}", module.id);
+        return Ok(false);
+    }
 
-            let tmp_dst = target_base.join(format!(".tmp_{}", module.id));
+    let filter = SyncFilter::new(config);
+    let manifest = Manifest::build(module, &filter, config.sync.hash)
+        .with_context(|| format!("Failed to build manifest for {}", module.id))?;
 
-            if tmp_dst.exists() {
-                let _ = fs::remove_dir_all(&tmp_dst);
-            }
+    let previous = if dst.exists() {
+        Manifest::load(&dst)
+    } else {
+        None
+    };
 
-            if let Err(e) = utils::sync_dir(&module.source_path, &tmp_dst, true) {
-                log::error!("Failed to sync module {}: {}", module.id, e);
-                let _ = fs::remove_dir_all(&tmp_dst);
-                return;
-            }
+    if !force && previous.as_ref() == Some(&manifest) {
+        log::debug!("Skipping module: {} (unchanged)", module.id);
+        return Ok(false);
+    }
 
-            if let Err(e) = utils::prune_empty_dirs(&tmp_dst) {
-                log::warn!("Failed to prune empty dirs for {}: {}", module.id, e);
-            }
+    log::info!("Syncing module: {} (Updated/New)", module.id);
 
-            if let Err(e) = apply_overlay_opaque_flags(&tmp_dst) {
-                log::warn!(
-                    "Failed to apply overlay opaque xattrs for {}: {}",
-                    module.id,
-                    e
-                );
-            }
+    let tmp_dst = target_base.join(format!(".tmp_{}", module.id));
 
-            let mut backup_created = false;
-            if dst.exists() {
-                if let Err(e) = fs::rename(&dst, &dst_backup) {
-                    log::error!("Failed to backup existing module {}: {}", module.id, e);
-                    let _ = fs::remove_dir_all(&tmp_dst);
-                    return;
-                }
-                backup_created = true;
-            }
+    if tmp_dst.exists() {
+        let _ = fs::remove_dir_all(&tmp_dst);
+    }
 
-            if let Err(e) = fs::rename(&tmp_dst, &dst) {
-                log::error!("Failed to commit atomic sync for {}: {}", module.id, e);
-                if backup_created {
-                    let _ = fs::rename(&dst_backup, &dst);
-                }
-                let _ = fs::remove_dir_all(&tmp_dst);
-                return;
-            }
+    // A forced sync copies everything, so a damaged copy can be repaired.
+    let empty = Manifest::default();
+    let previous = match &previous {
+        Some(previous) if !force => previous,
+        _ => &empty,
+    };
+
+    if let Err(e) = sync_delta(module, previous, &manifest, &dst, &tmp_dst) {
+        let _ = fs::remove_dir_all(&tmp_dst);
+        return Err(e);
+    }
+
+    let skipped = filter.skipped_bytes(module);
+    if skipped > 0 {
+        log::info!("Sync filter saved {} bytes for {}", skipped, module.id);
+    }
+
+    if let Err(e) = apply_masks(module, &tmp_dst) {
+        let _ = fs::remove_dir_all(&tmp_dst);
+        return Err(e).context(format!("Failed to mask conflicting files of {}", module.id));
+    }
+
+    if let Err(e) = utils::prune_empty_dirs(&tmp_dst) {
+        log::warn!("Failed to prune empty dirs for {}: {}", module.id, e);
+    }
+
+    if let Err(e) = apply_overlay_opaque_flags(&tmp_dst) {
+        log::warn!(
+            "Failed to apply overlay opaque xattrs for {}: {}",
+            module.id,
+            e
+        );
+    }
+
+    if let Err(e) = manifest.save(&tmp_dst) {
+        log::warn!("Failed to save manifest for {}: {:#}", module.id, e);
+    }
+
+    let mut backup_created = false;
+    if dst.exists() {
+        if let Err(e) = fs::rename(&dst, &dst_backup) {
+            let _ = fs::remove_dir_all(&tmp_dst);
+            return Err(e).context(format!("Failed to backup existing module {}", module.id));
+        }
+        backup_created = true;
+    }
+
+    if let Err(e) = fs::rename(&tmp_dst, &dst) {
+        if backup_created {
+            let _ = fs::rename(&dst_backup, &dst);
+        }
+        let _ = fs::remove_dir_all(&tmp_dst);
+        return Err(e).context(format!("Failed to commit atomic sync for {}", module.id));
+    }
 
-            if backup_created && let Err(e) = fs::remove_dir_all(&dst_backup) {
-                log::warn!("Failed to clean up backup for {}: {}", module.id, e);
+    if backup_created && let Err(e) = fs::remove_dir_all(&dst_backup) {
+        log::warn!("Failed to clean up backup for {}: {}", module.id, e);
+    }
+
+    Ok(true)
+}
+
+/// Builds the new copy in `tmp_dst` from the previous one in `dst`: entries
+/// whose manifest entry is unchanged are hard linked, everything else is
+/// copied from the source, and entries gone from the source are left out.
+/// `dst` itself is never modified, so it stays intact until the final swap.
+/// A file whose mode or xattrs changed is copied as well, since its inode is
+/// shared with the previous copy.
+fn sync_delta(
+    module: &Module,
+    previous: &Manifest,
+    current: &Manifest,
+    dst: &Path,
+    tmp_dst: &Path,
+) -> Result<()> {
+    let previous_entries: HashMap<&str, &ManifestEntry> = previous
+        .entries
+        .iter()
+        .map(|e| (e.path.as_str(), e))
+        .collect();
+
+    let (mut linked, mut copied) = (0usize, 0usize);
+
+    fs::create_dir_all(tmp_dst)?;
+
+    for entry in &current.entries {
+        let src = module.source_path.join(&entry.path);
+        let out = tmp_dst.join(&entry.path);
+        let metadata = src
+            .symlink_metadata()
+            .with_context(|| format!("Failed to stat {}", src.display()))?;
+
+        if metadata.is_dir() {
+            fs::create_dir_all(&out)?;
+            fs::set_permissions(&out, metadata.permissions())?;
+            let _ = utils::internal_copy_extended_attributes(&src, &out);
+            continue;
+        }
+
+        if previous_entries.get(entry.path.as_str()) == Some(&entry)
+            && fs::hard_link(dst.join(&entry.path), &out).is_ok()
+        {
+            linked += 1;
+            continue;
+        }
+
+        utils::copy_entry(&src, &out, &metadata)
+            .with_context(|| format!("Failed to copy {}", src.display()))?;
+        copied += 1;
+    }
+
+    let current_paths: HashSet<&str> = current.entries.iter().map(|e| e.path.as_str()).collect();
+    let removed = previous
+        .entries
+        .iter()
+        .filter(|e| !current_paths.contains(e.path.as_str()))
+        .count();
+
+    log::info!(
+        "Delta sync of {}: {} unchanged, {} copied, {} removed",
+        module.id,
+        linked,
+        copied,
+        removed
+    );
+
+    Ok(())
+}
+
+/// Removes the files `module` loses through `conflict_resolution`. They are
+/// recorded in the manifest, so a change in the resolution triggers a new
+/// sync.
+fn apply_masks(module: &Module, root: &Path) -> Result<()> {
+    for relative in &module.masked {
+        let path = root.join(relative);
+        match fs::remove_file(&path) {
+            Ok(()) => log::debug!("Masked {} of {}", relative, module.id),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                return Err(e).with_context(|| format!("failed to remove {}", path.display()));
             }
-        } else {
-            log::debug!("Skipping module: {}", module.id);
         }
-    });
+    }
 
     Ok(())
 }
@@ -127,25 +274,6 @@ fn prune_orphaned_modules(modules: &[Module], target_base: &Path) -> Result<()>
     Ok(())
 }
 
-fn should_sync(src: &Path, dst: &Path) -> bool {
-    if !dst.exists() {
-        return true;
-    }
-
-    let src_prop = src.join("module.prop");
-
-    let dst_prop = dst.join("module.prop");
-
-    if !src_prop.exists() || !dst_prop.exists() {
-        return true;
-    }
-
-    match (fs::read(&src_prop), fs::read(&dst_prop)) {
-        (Ok(s), Ok(d)) => s != d,
-        _ => true,
-    }
-}
-
 fn has_files_recursive(path: &Path) -> bool {
     if let Ok(entries) = fs::read_dir(path) {
         for entry in entries.flatten() {
@@ -157,3 +285,84 @@ fn has_files_recursive(path: &Path) -> bool {
 
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::MetadataExt;
+
+    use super::*;
+    use crate::core::inventory::{Dependencies, RuleOrigins};
+
+    fn inode(path: &Path) -> u64 {
+        fs::metadata(path).unwrap().ino()
+    }
+
+    #[test]
+    fn delta_links_unchanged_and_copies_changed_files() {
+        let source = tempfile::tempdir().unwrap();
+        let storage = tempfile::tempdir().unwrap();
+        let bin = source.path().join("system/bin");
+        fs::create_dir_all(&bin).unwrap();
+        fs::write(bin.join("same"), "same").unwrap();
+        fs::write(bin.join("changed"), "old").unwrap();
+        fs::write(bin.join("deleted"), "deleted").unwrap();
+
+        let config = Config::default();
+        let module = Module {
+            id: "m".to_string(),
+            source_path: source.path().to_path_buf(),
+            prop: Default::default(),
+            rules: Default::default(),
+            rule_origins: RuleOrigins::default(),
+            priority: 0,
+            dependencies: Dependencies::default(),
+            exclusion: None,
+            masked: Default::default(),
+        };
+        let dst = storage.path().join("m");
+        let tmp_dst = storage.path().join(".tmp_m");
+
+        assert!(sync_module(&module, storage.path(), false, &config).unwrap());
+        let synced = dst.join("system/bin");
+        let same_inode = inode(&synced.join("same"));
+        let changed_inode = inode(&synced.join("changed"));
+
+        fs::write(bin.join("changed"), "new content").unwrap();
+        fs::remove_file(bin.join("deleted")).unwrap();
+        fs::write(bin.join("added"), "added").unwrap();
+
+        let filter = SyncFilter::new(&config);
+        let previous = Manifest::load(&dst).unwrap();
+        let current = Manifest::build(&module, &filter, false).unwrap();
+        sync_delta(&module, &previous, &current, &dst, &tmp_dst).unwrap();
+
+        let staged = tmp_dst.join("system/bin");
+        assert_eq!(inode(&staged.join("same")), same_inode);
+        assert_ne!(inode(&staged.join("changed")), changed_inode);
+        assert_eq!(
+            fs::read_to_string(staged.join("changed")).unwrap(),
+            "new content"
+        );
+        assert_eq!(fs::read_to_string(staged.join("added")).unwrap(), "added");
+        assert!(!staged.join("deleted").exists());
+
+        // The previous copy is left alone until the swap.
+        assert_eq!(fs::read_to_string(synced.join("changed")).unwrap(), "old");
+        assert!(synced.join("deleted").exists());
+
+        fs::remove_dir_all(&tmp_dst).unwrap();
+        assert!(sync_module(&module, storage.path(), false, &config).unwrap());
+
+        assert_eq!(inode(&synced.join("same")), same_inode);
+        assert_eq!(
+            fs::read_to_string(synced.join("changed")).unwrap(),
+            "new content"
+        );
+        assert!(!synced.join("deleted").exists());
+        assert!(!tmp_dst.exists());
+        assert!(!storage.path().join(".backup_m").exists());
+
+        // Nothing changed since, so the copy is kept as it is.
+        assert!(!sync_module(&module, storage.path(), false, &config).unwrap());
+    }
+}
@@ -0,0 +1,97 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use loopdev::LoopDevice;
+use rustix::mount::{UnmountFlags, unmount};
+use serde::Serialize;
+
+use crate::{
+    core::{state::RuntimeState, storage},
+    mount::journal::{self, UnmountFailure, UnmountReport},
+    sys::mount::is_mounted,
+};
+
+#[derive(Debug, Default, Serialize)]
+pub struct TeardownReport {
+    #[serde(flatten)]
+    pub mounts: UnmountReport,
+    pub loop_devices: Vec<PathBuf>,
+    /// The storage backend or its loop device when they could not be
+    /// released.
+    pub storage_failed: Vec<UnmountFailure>,
+}
+
+// The device is only detached while it still backs the recorded image, as an
+// autocleared device number may have been handed to something else since.
+fn attached_loop_device(state: &RuntimeState) -> Option<PathBuf> {
+    let device = state.loop_device.clone()?;
+    let image = state.backing_image.as_ref()?;
+    let backing = storage::loop_backing_file(&device)?;
+
+    backing
+        .to_string_lossy()
+        .starts_with(&*image.to_string_lossy())
+        .then_some(device)
+}
+
+pub fn teardown() -> Result<TeardownReport> {
+    let mut state = RuntimeState::load().context("Failed to load runtime state")?;
+
+    log::info!(">> Teardown: reverting {} mount(s)...", state.mounts.len());
+
+    let mut report = TeardownReport {
+        mounts: journal::unmount_records(&state.mounts),
+        ..Default::default()
+    };
+
+    let storage = state.mount_point.clone();
+    if !storage.as_os_str().is_empty() && is_mounted(&storage) {
+        match unmount(&storage, UnmountFlags::DETACH) {
+            Ok(()) => report.mounts.unmounted.push(storage.clone()),
+            Err(e) => report.storage_failed.push(UnmountFailure {
+                target: storage.clone(),
+                error: e.to_string(),
+            }),
+        }
+    }
+
+    // Storage is mounted with autoclear, detaching here only makes it
+    // immediate once the lazy unmounts above release the device.
+    if let Some(device) = attached_loop_device(&state) {
+        match LoopDevice::open(&device).and_then(|ld| ld.detach()) {
+            Ok(()) => {
+                report.loop_devices.push(device);
+                state.loop_device = None;
+            }
+            Err(e) => report.storage_failed.push(UnmountFailure {
+                target: device,
+                error: e.to_string(),
+            }),
+        }
+    }
+
+    for failure in report.mounts.failed.iter().chain(&report.storage_failed) {
+        log::warn!(
+            "Could not remove {}: {}",
+            failure.target.display(),
+            failure.error
+        );
+    }
+
+    state
+        .mounts
+        .retain(|m| report.mounts.failed.iter().any(|f| f.target == m.target));
+    // The module lists describe what is still in place, so they are only
+    // dropped once nothing is left behind.
+    if state.mounts.is_empty() && report.storage_failed.is_empty() {
+        state.overlay_modules.clear();
+        state.magic_modules.clear();
+        state.active_mounts.clear();
+    }
+    state.save().context("Failed to save runtime state")?;
+
+    Ok(report)
+}
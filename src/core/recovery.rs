@@ -0,0 +1,535 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    conf::config::{Config, ModuleRules, MountMode},
+    core::ops::planner::{DiagnosticIssue, DiagnosticLevel},
+    defs, utils,
+};
+
+/// Consecutive boots that may start mounting without ever completing before
+/// the daemon falls back to safe mode.
+pub const FAILSAFE_THRESHOLD: u32 = 3;
+/// Incomplete boots with the same module set before bisection starts. Kept
+/// below `FAILSAFE_THRESHOLD` so modules are isolated before the config is
+/// reset.
+pub const BISECT_THRESHOLD: u32 = 2;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BootAttempt {
+    pub timestamp: u64,
+    pub modules: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SafeModeRecord {
+    pub timestamp: u64,
+    pub config_backup: Option<PathBuf>,
+    #[serde(default)]
+    pub dropin_backup: Option<PathBuf>,
+    pub modules: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Bisection {
+    pub round: u32,
+    pub suspects: Vec<String>,
+    pub disabled: Vec<String>,
+    pub culprit: Option<String>,
+}
+
+impl Bisection {
+    fn advance(&mut self) {
+        if self.suspects.len() <= 1 {
+            self.culprit = self.suspects.first().cloned();
+            self.disabled = self.suspects.clone();
+        } else {
+            self.disabled = self.suspects[..self.suspects.len() / 2].to_vec();
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BootRecord {
+    pub pending: u32,
+    #[serde(default)]
+    pub attempts: Vec<BootAttempt>,
+    #[serde(default)]
+    pub safe_mode: Option<SafeModeRecord>,
+    #[serde(default)]
+    pub bisection: Option<Bisection>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn record_path() -> &'static Path {
+    Path::new(defs::BOOT_RECORD_FILE)
+}
+
+impl BootRecord {
+    pub fn load() -> Result<Self> {
+        Self::load_from(record_path())
+    }
+
+    fn load_from(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(path)?;
+
+        let record = serde_json::from_str(&content)?;
+
+        Ok(record)
+    }
+
+    /// Written atomically, since a record torn by a reboot would be read back
+    /// as empty and reset the boot counter.
+    fn save_to(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+
+        utils::atomic_write(path, json)
+    }
+}
+
+/// Called right before mounts are applied. The counter stays raised until
+/// `boot-completed` clears it.
+pub fn begin_boot(modules: &[String]) -> Result<()> {
+    begin_boot_in(record_path(), modules)
+}
+
+fn begin_boot_in(path: &Path, modules: &[String]) -> Result<()> {
+    let mut record = BootRecord::load_from(path).unwrap_or_default();
+
+    record.pending += 1;
+    record.attempts.push(BootAttempt {
+        timestamp: now(),
+        modules: modules.to_vec(),
+    });
+
+    log::debug!("Boot attempt {} recorded", record.pending);
+
+    record.save_to(path).context("Failed to save boot record")
+}
+
+pub fn complete_boot() -> Result<()> {
+    complete_boot_in(record_path())
+}
+
+fn complete_boot_in(path: &Path) -> Result<()> {
+    let mut record = BootRecord::load_from(path).unwrap_or_default();
+
+    record.pending = 0;
+    record.attempts.clear();
+
+    record.save_to(path).context("Failed to save boot record")
+}
+
+/// Returns `true` when the previous boots kept failing and this boot must not
+/// mount anything. The config at `config_path` is backed up and replaced with
+/// the defaults, and `dropin_dir` is moved aside so nothing is layered over
+/// them.
+pub fn check_failsafe(config_path: &Path, dropin_dir: Option<&Path>) -> Result<bool> {
+    check_failsafe_in(record_path(), config_path, dropin_dir)
+}
+
+fn check_failsafe_in(path: &Path, config_path: &Path, dropin_dir: Option<&Path>) -> Result<bool> {
+    let mut record = BootRecord::load_from(path).unwrap_or_else(|e| {
+        log::warn!("Failed to read boot record, starting fresh: {:#}", e);
+        BootRecord::default()
+    });
+
+    if record.pending < FAILSAFE_THRESHOLD {
+        return Ok(false);
+    }
+
+    log::error!(
+        "!! {} consecutive boots did not complete. Entering safe mode.",
+        record.pending
+    );
+
+    let config_backup = if config_path.exists() {
+        let backup = PathBuf::from(format!("{}.failsafe", config_path.display()));
+        fs::copy(config_path, &backup)
+            .with_context(|| format!("Failed to back up {}", config_path.display()))?;
+        Some(backup)
+    } else {
+        None
+    };
+
+    Config::default()
+        .save_to_file(config_path)
+        .context("Failed to restore default config")?;
+
+    let dropin_backup = match dropin_dir.filter(|dir| dir.exists()) {
+        Some(dir) => {
+            let backup = PathBuf::from(format!("{}.failsafe", dir.display()));
+            if backup.exists() {
+                fs::remove_dir_all(&backup)
+                    .with_context(|| format!("Failed to remove {}", backup.display()))?;
+            }
+            fs::rename(dir, &backup)
+                .with_context(|| format!("Failed to move {} aside", dir.display()))?;
+            Some(backup)
+        }
+        None => None,
+    };
+
+    let modules: BTreeSet<String> = record
+        .attempts
+        .iter()
+        .flat_map(|a| a.modules.iter().cloned())
+        .collect();
+
+    log::warn!("Modules active in the failing boots: {:?}", modules);
+
+    record.pending = 0;
+    record.attempts.clear();
+    record.safe_mode = Some(SafeModeRecord {
+        timestamp: now(),
+        config_backup,
+        dropin_backup,
+        modules: modules.into_iter().collect(),
+    });
+
+    record.save_to(path).context("Failed to save boot record")?;
+
+    Ok(true)
+}
+
+fn same_module_set(attempts: &[BootAttempt]) -> bool {
+    attempts.len() >= BISECT_THRESHOLD as usize
+        && attempts
+            .iter()
+            .rev()
+            .take(BISECT_THRESHOLD as usize)
+            .all(|a| a.modules == attempts[attempts.len() - 1].modules)
+}
+
+fn module_rule_keys(config: &Config, module_id: &str) -> Vec<String> {
+    let mut keys: Vec<String> = config
+        .rules
+        .get(module_id)
+        .map(|r| {
+            r.paths
+                .keys()
+                .chain(r.overrides.iter().flat_map(|o| o.paths.keys()))
+                .cloned()
+                .collect()
+        })
+        .unwrap_or_default();
+
+    keys.extend(
+        config
+            .partition_rule_patterns()
+            .into_iter()
+            .map(|(pattern, _)| pattern),
+    );
+
+    let rules_file = config.moduledir.join(module_id).join("hybrid_rules.json");
+    if let Ok(content) = fs::read_to_string(rules_file)
+        && let Ok(value) = serde_json::from_str::<serde_json::Value>(&content)
+    {
+        let overrides = value
+            .get("overrides")
+            .and_then(|o| o.as_array())
+            .into_iter()
+            .flatten();
+        for paths in std::iter::once(&value)
+            .chain(overrides)
+            .filter_map(|v| v.get("paths").and_then(|p| p.as_object()))
+        {
+            keys.extend(paths.keys().cloned());
+        }
+    }
+
+    keys
+}
+
+fn ignore_modules(config: &mut Config, modules: &[String]) {
+    for id in modules {
+        // Path rules win over the default mode, so every known pattern has to
+        // be overridden as well for the module to be fully ignored.
+        let paths: HashMap<String, MountMode> = module_rule_keys(config, id)
+            .into_iter()
+            .map(|k| (k, MountMode::Ignore))
+            .collect();
+
+        config.rules.insert(
+            id.clone(),
+            ModuleRules {
+                default_mode: MountMode::Ignore,
+                paths,
+                overrides: Vec::new(),
+            },
+        );
+    }
+}
+
+/// Advances module bisection using the outcome of the previous boot and
+/// ignores the modules under test through `config.rules`. Nothing is written
+/// to the module directories or the config file.
+pub fn apply_bisection(config: &mut Config) -> Result<()> {
+    apply_bisection_in(record_path(), config)
+}
+
+fn apply_bisection_in(path: &Path, config: &mut Config) -> Result<()> {
+    let mut record = BootRecord::load_from(path).unwrap_or_default();
+    let last_failed = record.pending > 0;
+
+    match record.bisection.as_mut() {
+        Some(bisection) if bisection.culprit.is_none() => {
+            bisection.suspects = if last_failed {
+                let disabled = &bisection.disabled;
+                bisection
+                    .suspects
+                    .iter()
+                    .filter(|id| !disabled.contains(id))
+                    .cloned()
+                    .collect()
+            } else {
+                bisection.disabled.clone()
+            };
+            bisection.round += 1;
+            bisection.advance();
+
+            record.pending = 0;
+            record.attempts.clear();
+        }
+        None if record.pending >= BISECT_THRESHOLD && same_module_set(&record.attempts) => {
+            let suspects = record
+                .attempts
+                .last()
+                .map(|a| a.modules.clone())
+                .unwrap_or_default();
+
+            if suspects.is_empty() {
+                return Ok(());
+            }
+
+            log::error!(
+                "!! {} consecutive boots failed with the same modules. Starting bisection.",
+                record.pending
+            );
+
+            let mut bisection = Bisection {
+                round: 1,
+                suspects,
+                ..Default::default()
+            };
+            bisection.advance();
+            record.bisection = Some(bisection);
+
+            record.pending = 0;
+            record.attempts.clear();
+        }
+        _ => {}
+    }
+
+    let Some(bisection) = &record.bisection else {
+        return Ok(());
+    };
+
+    match &bisection.culprit {
+        Some(culprit) => log::warn!("!! Module {} is ignored as bootloop culprit", culprit),
+        None => log::warn!(
+            ">> Bisection round {}: {} suspect(s), ignoring {:?}",
+            bisection.round,
+            bisection.suspects.len(),
+            bisection.disabled
+        ),
+    }
+
+    ignore_modules(config, &bisection.disabled);
+
+    record.save_to(path).context("Failed to save boot record")
+}
+
+pub fn reset_bisection() -> Result<()> {
+    let path = record_path();
+    let mut record = BootRecord::load_from(path).unwrap_or_default();
+
+    record.bisection = None;
+
+    record.save_to(path).context("Failed to save boot record")
+}
+
+pub fn diagnostics() -> Vec<DiagnosticIssue> {
+    let Ok(record) = BootRecord::load() else {
+        return Vec::new();
+    };
+
+    let Some(bisection) = record.bisection else {
+        return Vec::new();
+    };
+
+    let issue = match bisection.culprit {
+        Some(culprit) => DiagnosticIssue {
+            level: DiagnosticLevel::Critical,
+            context: culprit.clone(),
+            message: format!(
+                "Module isolated as the cause of repeated boot failures after {} round(s) and is \
+                 ignored. Run `bisect-reset` to mount it again.",
+                bisection.round
+            ),
+        },
+        None => DiagnosticIssue {
+            level: DiagnosticLevel::Warning,
+            context: "bisection".to_string(),
+            message: format!(
+                "Boot failure bisection in progress (round {}): suspects {:?}, ignored {:?}",
+                bisection.round, bisection.suspects, bisection.disabled
+            ),
+        },
+    };
+
+    vec![issue]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    fn ignored(config: &Config) -> Vec<String> {
+        let mut ids: Vec<String> = config
+            .rules
+            .iter()
+            .filter(|(_, rules)| rules.default_mode == MountMode::Ignore)
+            .map(|(id, _)| id.clone())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn failsafe_after_threshold_of_incomplete_boots() {
+        let dir = tempfile::tempdir().unwrap();
+        let record = dir.path().join("boot_record.json");
+        let config_path = dir.path().join("config.toml");
+        let dropin_dir = dir.path().join("config.d");
+        fs::write(&config_path, "disable_umount = true\n").unwrap();
+        fs::create_dir(&dropin_dir).unwrap();
+        fs::write(dropin_dir.join("10-extra.toml"), "").unwrap();
+
+        for _ in 1..FAILSAFE_THRESHOLD {
+            begin_boot_in(&record, &ids(&["a"])).unwrap();
+            assert!(!check_failsafe_in(&record, &config_path, Some(&dropin_dir)).unwrap());
+        }
+        begin_boot_in(&record, &ids(&["b"])).unwrap();
+        assert!(check_failsafe_in(&record, &config_path, Some(&dropin_dir)).unwrap());
+
+        let backup = dir.path().join("config.toml.failsafe");
+        assert_eq!(
+            fs::read_to_string(&backup).unwrap(),
+            "disable_umount = true\n"
+        );
+        assert!(!dropin_dir.exists());
+        assert!(dir.path().join("config.d.failsafe/10-extra.toml").exists());
+
+        let saved = BootRecord::load_from(&record).unwrap();
+        assert_eq!(saved.pending, 0);
+        assert!(saved.attempts.is_empty());
+        let safe_mode = saved.safe_mode.unwrap();
+        assert_eq!(safe_mode.modules, ids(&["a", "b"]));
+        assert_eq!(safe_mode.config_backup, Some(backup));
+
+        // The counter starts over, so the next boot mounts normally.
+        assert!(!check_failsafe_in(&record, &config_path, Some(&dropin_dir)).unwrap());
+    }
+
+    #[test]
+    fn completed_boot_resets_the_counter() {
+        let dir = tempfile::tempdir().unwrap();
+        let record = dir.path().join("boot_record.json");
+        let config_path = dir.path().join("config.toml");
+
+        for _ in 1..FAILSAFE_THRESHOLD {
+            begin_boot_in(&record, &ids(&["a"])).unwrap();
+        }
+        complete_boot_in(&record).unwrap();
+        begin_boot_in(&record, &ids(&["a"])).unwrap();
+
+        let saved = BootRecord::load_from(&record).unwrap();
+        assert_eq!(saved.pending, 1);
+        assert_eq!(saved.attempts.len(), 1);
+        assert!(!check_failsafe_in(&record, &config_path, None).unwrap());
+        assert!(!config_path.exists());
+    }
+
+    #[test]
+    fn bisection_needs_repeated_failures_with_the_same_modules() {
+        let dir = tempfile::tempdir().unwrap();
+        let record = dir.path().join("boot_record.json");
+        let mut config = Config::default();
+
+        begin_boot_in(&record, &ids(&["a", "b"])).unwrap();
+        apply_bisection_in(&record, &mut config).unwrap();
+        assert!(BootRecord::load_from(&record).unwrap().bisection.is_none());
+
+        begin_boot_in(&record, &ids(&["a", "c"])).unwrap();
+        apply_bisection_in(&record, &mut config).unwrap();
+        assert!(BootRecord::load_from(&record).unwrap().bisection.is_none());
+        assert!(ignored(&config).is_empty());
+    }
+
+    #[test]
+    fn bisection_narrows_down_to_the_culprit() {
+        let dir = tempfile::tempdir().unwrap();
+        let record = dir.path().join("boot_record.json");
+        let modules = ids(&["a", "b", "c", "d"]);
+        let bisect = || {
+            let mut config = Config::default();
+            apply_bisection_in(&record, &mut config).unwrap();
+            let bisection = BootRecord::load_from(&record).unwrap().bisection.unwrap();
+            (bisection, ignored(&config))
+        };
+
+        for _ in 0..BISECT_THRESHOLD {
+            begin_boot_in(&record, &modules).unwrap();
+        }
+        let (bisection, ignored) = bisect();
+        assert_eq!(bisection.round, 1);
+        assert_eq!(bisection.suspects, modules);
+        assert_eq!(ignored, ids(&["a", "b"]));
+        assert_eq!(BootRecord::load_from(&record).unwrap().pending, 0);
+
+        // Failing with a and b ignored clears them.
+        begin_boot_in(&record, &ids(&["c", "d"])).unwrap();
+        let (bisection, ignored) = bisect();
+        assert_eq!(bisection.round, 2);
+        assert_eq!(bisection.suspects, ids(&["c", "d"]));
+        assert_eq!(ignored, ids(&["c"]));
+
+        // Booting with c ignored convicts it.
+        begin_boot_in(&record, &ids(&["a", "b", "d"])).unwrap();
+        complete_boot_in(&record).unwrap();
+        let (bisection, ignored) = bisect();
+        assert_eq!(bisection.round, 3);
+        assert_eq!(bisection.culprit.as_deref(), Some("c"));
+        assert_eq!(ignored, ids(&["c"]));
+
+        // The culprit stays ignored on later boots.
+        let (bisection, ignored) = bisect();
+        assert_eq!(bisection.round, 3);
+        assert_eq!(ignored, ids(&["c"]));
+    }
+}
@@ -1,4 +1,5 @@
 use std::{
+    collections::BTreeMap,
     fs,
     path::PathBuf,
     time::{SystemTime, UNIX_EPOCH},
@@ -7,7 +8,11 @@ use std::{
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
-use crate::{defs, utils::fs::xattr};
+use crate::{
+    defs,
+    mount::journal::{MountRecord, PhaseRollback},
+    sys::kernel::{self, KernelCapabilities},
+};
 
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct RuntimeState {
@@ -15,14 +20,26 @@ pub struct RuntimeState {
     pub pid: u32,
     pub storage_mode: String,
     pub mount_point: PathBuf,
+    #[serde(default)]
+    pub backing_image: Option<PathBuf>,
+    #[serde(default)]
+    pub loop_device: Option<PathBuf>,
     pub overlay_modules: Vec<String>,
     pub magic_modules: Vec<String>,
     #[serde(default)]
+    pub magic_scopes: BTreeMap<String, Vec<PathBuf>>,
+    #[serde(default)]
     pub active_mounts: Vec<String>,
     #[serde(default)]
     pub zygisksu_enforce: bool,
     #[serde(default)]
     pub tmpfs_xattr_supported: bool,
+    #[serde(default)]
+    pub capabilities: KernelCapabilities,
+    #[serde(default)]
+    pub rollbacks: Vec<PhaseRollback>,
+    #[serde(default)]
+    pub mounts: Vec<MountRecord>,
 }
 
 impl RuntimeState {
@@ -30,9 +47,14 @@ impl RuntimeState {
     pub fn new(
         storage_mode: String,
         mount_point: PathBuf,
+        backing_image: Option<PathBuf>,
+        loop_device: Option<PathBuf>,
         overlay_modules: Vec<String>,
         magic_modules: Vec<String>,
+        magic_scopes: BTreeMap<String, Vec<PathBuf>>,
         active_mounts: Vec<String>,
+        rollbacks: Vec<PhaseRollback>,
+        mounts: Vec<MountRecord>,
     ) -> Self {
         let start = SystemTime::now();
 
@@ -44,18 +66,25 @@ impl RuntimeState {
         let pid = std::process::id();
 
         let zygisksu_enforce = crate::utils::check_zygisksu_enforce_status();
-        let tmpfs_xattr_supported = xattr::is_overlay_xattr_supported().unwrap_or(false);
+        let capabilities = kernel::capabilities().clone();
+        let tmpfs_xattr_supported = capabilities.tmpfs_xattr;
 
         Self {
             timestamp,
             pid,
             storage_mode,
             mount_point,
+            backing_image,
+            loop_device,
             overlay_modules,
             magic_modules,
+            magic_scopes,
             active_mounts,
             zygisksu_enforce,
             tmpfs_xattr_supported,
+            capabilities,
+            rollbacks,
+            mounts,
         }
     }
 
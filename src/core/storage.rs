@@ -9,15 +9,21 @@ use anyhow::{Context, Result, bail, ensure};
 use jwalk::WalkDir;
 use loopdev::LoopControl;
 use rustix::mount::{
-    MountFlags, MountPropagationFlags, UnmountFlags, mount, mount_change, unmount as umount,
+    MountFlags, MountPropagationFlags, UnmountFlags, mount, mount_bind, mount_change,
+    unmount as umount,
 };
 
 #[cfg(any(target_os = "linux", target_os = "android"))]
 use crate::mount::umount_mgr::send_umountable;
 use crate::{
+    core::ops::manifest::ImageManifest,
     defs,
     mount::overlayfs::utils as overlay_utils,
-    sys::{mount::is_mounted, nuke},
+    sys::{
+        kernel,
+        mount::{is_mounted, mount_source},
+        nuke,
+    },
     utils::{self, ensure_dir_exists, lsetfilecon},
 };
 
@@ -28,10 +34,23 @@ pub struct StorageHandle {
     pub mode: String,
     pub backing_image: Option<PathBuf>,
     pub final_target: Option<PathBuf>,
+    pub loop_device: Option<PathBuf>,
 }
 
 impl StorageHandle {
-    pub fn commit(&mut self, disable_umount: bool) -> Result<()> {
+    /// Whether the EROFS image left by a previous boot was packed from
+    /// modules described by `manifest`, so it can be mounted as it is.
+    pub fn image_is_current(&self, manifest: &ImageManifest) -> bool {
+        self.mode == "erofs_staging"
+            && self.backing_image.as_ref().is_some_and(|image| {
+                image.exists() && ImageManifest::load(image).as_ref() == Some(manifest)
+            })
+    }
+
+    /// Packs the staging directory into the EROFS image and mounts it. With
+    /// a `manifest` matching the existing image, packing is skipped;
+    /// otherwise the manifest is saved once the new image is packed.
+    pub fn commit(&mut self, disable_umount: bool, manifest: Option<&ImageManifest>) -> Result<()> {
         if self.mode == "erofs_staging" {
             let image_path = self
                 .backing_image
@@ -43,8 +62,20 @@ impl StorageHandle {
                 .as_ref()
                 .context("EROFS final target missing")?;
 
-            create_erofs_image(&self.mount_point, image_path)
-                .context("Failed to pack EROFS image")?;
+            if manifest.is_some_and(|m| self.image_is_current(m)) {
+                log::info!("EROFS image is up to date, skipping repack.");
+            } else {
+                remove_erofs_image(image_path);
+
+                create_erofs_image(&self.mount_point, image_path)
+                    .context("Failed to pack EROFS image")?;
+
+                if let Some(manifest) = manifest
+                    && let Err(e) = manifest.save(image_path)
+                {
+                    log::warn!("Failed to save EROFS image manifest: {:#}", e);
+                }
+            }
 
             if let Err(e) = umount(&self.mount_point, UnmountFlags::DETACH) {
                 log::warn!("Failed to unmount staging tmpfs: {}", e);
@@ -56,7 +87,7 @@ impl StorageHandle {
 
             ensure_dir_exists(final_target)?;
 
-            mount_erofs_image(image_path, final_target)
+            let device = mount_erofs_image(image_path, final_target)
                 .context("Failed to mount finalized EROFS image")?;
 
             if let Err(e) = mount_change(final_target, MountPropagationFlags::PRIVATE) {
@@ -71,10 +102,58 @@ impl StorageHandle {
             self.mount_point = final_target.clone();
             self.mode = "erofs".to_string();
             self.final_target = None;
+            self.loop_device = Some(device);
         }
 
         Ok(())
     }
+
+    /// Keeps the storage reachable through a private bind under the run
+    /// directory, so it survives the unmount of the temporary mount base at
+    /// the end of boot and `reload` can still sync into it.
+    pub fn retain(&self, disable_umount: bool) -> Result<PathBuf> {
+        let target = Path::new(defs::STORAGE_DIR);
+
+        if is_mounted(target) {
+            let _ = umount(target, UnmountFlags::DETACH);
+        }
+        ensure_dir_exists(target)?;
+
+        mount_bind(&self.mount_point, target).with_context(|| {
+            format!(
+                "Failed to bind {} to {}",
+                self.mount_point.display(),
+                target.display()
+            )
+        })?;
+
+        if let Err(e) = mount_change(target, MountPropagationFlags::PRIVATE) {
+            log::warn!("Failed to make retained storage private: {}", e);
+        }
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if !disable_umount {
+            let _ = send_umountable(target);
+        }
+
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        let _ = disable_umount;
+
+        Ok(target.to_path_buf())
+    }
+}
+
+fn is_loop_device(source: &str) -> bool {
+    source.starts_with("/dev/block/loop") || source.starts_with("/dev/loop")
+}
+
+/// The image a loop device is currently attached to, if any.
+pub fn loop_backing_file(device: &Path) -> Option<PathBuf> {
+    let name = device.file_name()?;
+    let backing =
+        fs::read_to_string(Path::new("/sys/block").join(name).join("loop/backing_file")).ok()?;
+
+    Some(PathBuf::from(backing.trim_end()))
 }
 
 fn calculate_total_size(path: &Path) -> Result<u64> {
@@ -123,11 +202,10 @@ pub fn setup(
     {
         log::warn!("Failed to remove old ext4 image: {}", e);
     }
-    let erofs_path = img_path.with_extension("erofs");
-    if erofs_path.exists()
-        && let Err(e) = fs::remove_file(&erofs_path)
-    {
-        log::warn!("Failed to remove old erofs image: {}", e);
+    let caps = kernel::capabilities();
+
+    if !(use_erofs && caps.erofs) {
+        remove_erofs_image(&img_path.with_extension("erofs"));
     }
 
     if is_mounted(mnt_base) {
@@ -150,7 +228,11 @@ pub fn setup(
         }
     };
 
-    if use_erofs && is_erofs_supported() {
+    if use_erofs && !caps.erofs {
+        log::warn!("EROFS requested but not supported by the kernel, falling back.");
+    }
+
+    if use_erofs && caps.erofs {
         let erofs_path = img_path.with_extension("erofs");
         let staging_dir = Path::new(defs::RUN_DIR).join("erofs_staging");
 
@@ -172,6 +254,7 @@ pub fn setup(
             mode: "erofs_staging".to_string(),
             backing_image: Some(erofs_path),
             final_target: Some(mnt_base.to_path_buf()),
+            loop_device: None,
         });
     }
 
@@ -185,6 +268,7 @@ pub fn setup(
             mode: "tmpfs".to_string(),
             backing_image: None,
             final_target: None,
+            loop_device: None,
         });
     }
 
@@ -197,14 +281,27 @@ pub fn setup(
     Ok(handle)
 }
 
+/// Removes an EROFS image together with its manifest, so a manifest never
+/// describes an image it was not saved for.
+fn remove_erofs_image(image_path: &Path) {
+    for path in [ImageManifest::path(image_path), image_path.to_path_buf()] {
+        if path.exists()
+            && let Err(e) = fs::remove_file(&path)
+        {
+            log::warn!("Failed to remove old {}: {}", path.display(), e);
+        }
+    }
+}
+
 fn try_setup_tmpfs(target: &Path, mount_source: &str) -> Result<bool> {
+    if !kernel::capabilities().tmpfs_xattr {
+        log::info!("Tmpfs does not support trusted xattrs, skipping tmpfs backend.");
+        return Ok(false);
+    }
+
     if crate::sys::mount::mount_tmpfs(target, mount_source).is_ok() {
-        if utils::is_overlay_xattr_supported().unwrap_or(false) {
-            log::info!("Tmpfs mounted and supports xattrs (CONFIG_TMPFS_XATTR=y).");
-            return Ok(true);
-        } else {
-            let _ = umount(target, UnmountFlags::DETACH);
-        }
+        log::info!("Tmpfs mounted and supports xattrs (CONFIG_TMPFS_XATTR=y).");
+        return Ok(true);
     }
 
     Ok(false)
@@ -248,6 +345,10 @@ fn setup_ext4_image(target: &Path, img_path: &Path, moduledir: &Path) -> Result<
         }
     }
 
+    let loop_device = mount_source(target)
+        .filter(|source| is_loop_device(source))
+        .map(PathBuf::from);
+
     nuke::nuke_path(img_path);
 
     for dir_entry in WalkDir::new(target).parallelism(jwalk::Parallelism::Serial) {
@@ -261,15 +362,10 @@ fn setup_ext4_image(target: &Path, img_path: &Path, moduledir: &Path) -> Result<
         mode: "ext4".to_string(),
         backing_image: Some(img_path.to_path_buf()),
         final_target: None,
+        loop_device,
     })
 }
 
-fn is_erofs_supported() -> bool {
-    fs::read_to_string("/proc/filesystems")
-        .map(|content| content.contains("erofs"))
-        .unwrap_or(false)
-}
-
 fn create_erofs_image(src_dir: &Path, image_path: &Path) -> Result<()> {
     let mkfs_bin = Path::new(defs::MKFS_EROFS_PATH);
     let cmd_name = if mkfs_bin.exists() {
@@ -299,7 +395,7 @@ fn create_erofs_image(src_dir: &Path, image_path: &Path) -> Result<()> {
     Ok(())
 }
 
-fn mount_erofs_image(image_path: &Path, target: &Path) -> Result<()> {
+fn mount_erofs_image(image_path: &Path, target: &Path) -> Result<PathBuf> {
     ensure_dir_exists(target)?;
     lsetfilecon(image_path, "u:object_r:ksu_file:s0").ok();
 
@@ -332,5 +428,5 @@ fn mount_erofs_image(image_path: &Path, target: &Path) -> Result<()> {
         bail!("EROFS mount success but directory is empty (Loop device failure?)");
     }
 
-    Ok(())
+    Ok(device_path)
 }
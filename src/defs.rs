@@ -1,6 +1,8 @@
 pub const MODULES_IMG_FILE: &str = "/data/adb/hybrid-mount/modules.img";
 pub const RUN_DIR: &str = "/data/adb/hybrid-mount/run/";
+pub const STORAGE_DIR: &str = "/data/adb/hybrid-mount/run/storage";
 pub const STATE_FILE: &str = "/data/adb/hybrid-mount/run/daemon_state.json";
+pub const BOOT_RECORD_FILE: &str = "/data/adb/hybrid-mount/run/boot_record.json";
 pub const DISABLE_FILE_NAME: &str = "disable";
 pub const REMOVE_FILE_NAME: &str = "remove";
 pub const SKIP_MOUNT_FILE_NAME: &str = "skip_mount";
@@ -8,6 +10,7 @@ pub const SYSTEM_RW_DIR: &str = "/data/adb/hybrid-mount/rw";
 pub const MODULE_PROP_FILE: &str = "/data/adb/modules/hybrid-mount/module.prop";
 pub const MODULES_DIR: &str = "/data/adb/modules";
 pub const CONFIG_FILE: &str = "/data/adb/hybrid-mount/config.toml";
+pub const CONFIG_DROPIN_DIR: &str = "/data/adb/hybrid-mount/config.d";
 pub const MKFS_EROFS_PATH: &str = "/data/adb/metamodule/tools/mkfs.erofs";
 pub const POACEAE_MOUNT_POINT: &str = "/data/adb/poaceaefs_mount";
 pub const ZYGISKSU_DENYLIST_FILE: &str = "/data/adb/zygisksu/denylist_enforce";
@@ -60,4 +63,5 @@ pub const SENSITIVE_PARTITIONS: &[&str] = &[
 ];
 
 pub const REPLACE_DIR_FILE_NAME: &str = ".replace";
+pub const MANIFEST_FILE_NAME: &str = ".hybrid_manifest.json";
 pub const REPLACE_DIR_XATTR: &str = "trusted.overlay.opaque";
@@ -21,6 +21,11 @@ use mimalloc::MiMalloc;
 static GLOBAL: MiMalloc = MiMalloc;
 
 fn load_config(cli: &Cli) -> Result<Config> {
+    let (config_path, _) = cli_handlers::config_target(cli);
+    if let Err(e) = Config::migrate_file(&config_path) {
+        log::warn!("Failed to migrate {}: {:#}", config_path.display(), e);
+    }
+
     if let Some(config_path) = &cli.config {
         return Config::from_file(config_path).with_context(|| {
             format!(
@@ -47,6 +52,10 @@ fn load_config(cli: &Cli) -> Result<Config> {
 
 fn load_final_config(cli: &Cli) -> Result<Config> {
     let mut config = load_config(cli)?;
+    if let Err(e) = config.apply_active_profile() {
+        log::warn!("Ignoring config profile: {:#}", e);
+    }
+    config.apply_overrides(sys::props::source());
     config.merge_with_cli(
         cli.moduledir.clone(),
         cli.mountsource.clone(),
@@ -69,17 +78,38 @@ fn main() -> Result<()> {
 
     let cli = Cli::parse();
 
+    if let Some(path) = &cli.props {
+        sys::props::install(Box::new(sys::props::StaticProperties::from_file(path)?));
+    }
+
     if let Some(command) = &cli.command {
         match command {
             Commands::GenConfig { output } => cli_handlers::handle_gen_config(output)?,
-            Commands::ShowConfig => cli_handlers::handle_show_config(&cli)?,
-            Commands::SaveConfig { payload } => cli_handlers::handle_save_config(payload)?,
-            Commands::SaveModuleRules { module, payload } => {
-                cli_handlers::handle_save_module_rules(module, payload)?
+            Commands::ShowConfig { sources } => cli_handlers::handle_show_config(&cli, *sources)?,
+            Commands::ValidateConfig { input, probe } => {
+                cli_handlers::handle_validate_config(&cli, input.as_ref(), *probe)?
+            }
+            Commands::SaveConfig { input, revision } => {
+                cli_handlers::handle_save_config(&cli, input, revision.as_deref())?
+            }
+            Commands::PatchConfig { input, revision } => {
+                cli_handlers::handle_patch_config(&cli, input, revision.as_deref())?
+            }
+            Commands::RestoreConfig { index } => cli_handlers::handle_restore_config(&cli, *index)?,
+            Commands::SaveModuleRules { module, input } => {
+                cli_handlers::handle_save_module_rules(module, input)?
             }
             Commands::Modules => cli_handlers::handle_modules(&cli)?,
+            Commands::Plan => cli_handlers::handle_plan(&cli)?,
+            Commands::Explain { path } => cli_handlers::handle_explain(&cli, path)?,
             Commands::Conflicts => cli_handlers::handle_conflicts(&cli)?,
             Commands::Diagnostics => cli_handlers::handle_diagnostics(&cli)?,
+            Commands::BootCompleted => cli_handlers::handle_boot_completed()?,
+            Commands::BisectReset => cli_handlers::handle_bisect_reset()?,
+            Commands::Doctor { probe } => cli_handlers::handle_doctor(*probe)?,
+            Commands::Teardown => cli_handlers::handle_teardown()?,
+            Commands::Reload { module } => cli_handlers::handle_reload(&cli, module)?,
+            Commands::Profile { action } => cli_handlers::handle_profile(&cli, action)?,
             Commands::Poaceae { target, action } => cli_handlers::handle_poaceae(target, action)?,
         }
 
@@ -112,9 +142,16 @@ fn main() -> Result<()> {
 
     log::debug!("Process camouflaged as: {}", camouflage_name);
 
-    if let Ok(version) = std::fs::read_to_string("/proc/sys/kernel/osrelease") {
-        log::debug!("Kernel Version: {}", version.trim());
-    }
+    let caps = sys::kernel::capabilities();
+    log::debug!("Kernel Version: {}", caps.kernel_release);
+    log::info!(
+        ">> Kernel: overlay={}, erofs={}, tmpfs_xattr={}, fsopen={}, max_layers={}",
+        caps.overlay,
+        caps.erofs,
+        caps.tmpfs_xattr,
+        caps.fsopen,
+        caps.overlay_max_layers
+    );
 
     utils::check_ksu();
 
@@ -122,6 +159,21 @@ fn main() -> Result<()> {
         log::warn!("!! Umount is DISABLED via config.");
     }
 
+    let (config_path, dropin_dir) = cli_handlers::config_target(&cli);
+
+    if let Err(e) = core::recovery::apply_bisection(&mut config) {
+        log::warn!("Failed to apply boot bisection: {:#}", e);
+    }
+
+    match core::recovery::check_failsafe(&config_path, dropin_dir) {
+        Ok(true) => {
+            log::warn!("!! Safe mode: default config restored, skipping all mounts this boot.");
+            return Ok(());
+        }
+        Ok(false) => {}
+        Err(e) => log::error!("Failed to check for safe mode, mounting anyway: {:#}", e),
+    }
+
     let mnt_base = utils::get_mnt();
     let img_path = PathBuf::from(defs::MODULES_IMG_FILE);
 
@@ -0,0 +1,141 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{LazyLock, Mutex},
+};
+
+use rustix::{
+    io::Errno,
+    mount::{UnmountFlags, unmount},
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MountKind {
+    Overlay,
+    Staging,
+    Bind,
+    MagicBind,
+    MagicTmpfs,
+    Workspace,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MountRecord {
+    pub kind: MountKind,
+    pub target: PathBuf,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modules: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnmountFailure {
+    pub target: PathBuf,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UnmountReport {
+    pub unmounted: Vec<PathBuf>,
+    pub failed: Vec<UnmountFailure>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PhaseRollback {
+    pub phase: String,
+    pub reason: String,
+    #[serde(flatten)]
+    pub report: UnmountReport,
+}
+
+static JOURNAL: LazyLock<Mutex<Vec<MountRecord>>> = LazyLock::new(|| Mutex::new(Vec::new()));
+
+pub fn record<P>(kind: MountKind, target: P)
+where
+    P: AsRef<Path>,
+{
+    match JOURNAL.lock() {
+        Ok(mut journal) => journal.push(MountRecord {
+            kind,
+            target: target.as_ref().to_path_buf(),
+            modules: Vec::new(),
+        }),
+        Err(_) => log::warn!(
+            "Failed to lock mount journal, {} is not recorded",
+            target.as_ref().display()
+        ),
+    }
+}
+
+pub fn mark() -> usize {
+    JOURNAL.lock().map(|journal| journal.len()).unwrap_or(0)
+}
+
+/// Attributes every mount recorded since `mark` to `modules`.
+pub fn claim_since(mark: usize, modules: &[String]) {
+    if let Ok(mut journal) = JOURNAL.lock() {
+        for record in journal.iter_mut().skip(mark) {
+            record.modules = modules.to_vec();
+        }
+    }
+}
+
+pub fn entries() -> Vec<MountRecord> {
+    JOURNAL
+        .lock()
+        .map(|journal| journal.clone())
+        .unwrap_or_default()
+}
+
+/// Unmounts everything recorded since `mark`, newest first, and forgets it.
+pub fn rollback(mark: usize) -> UnmountReport {
+    let records = match JOURNAL.lock() {
+        Ok(mut journal) => {
+            let mark = mark.min(journal.len());
+            journal.split_off(mark)
+        }
+        Err(_) => {
+            log::error!("Failed to lock mount journal, nothing to roll back");
+            return UnmountReport::default();
+        }
+    };
+
+    unmount_records(&records)
+}
+
+pub fn unmount_records(records: &[MountRecord]) -> UnmountReport {
+    let mut report = UnmountReport::default();
+
+    for record in records.iter().rev() {
+        match unmount(&record.target, UnmountFlags::DETACH) {
+            Ok(()) => {
+                log::debug!(
+                    "Reverted {:?} mount on {}",
+                    record.kind,
+                    record.target.display()
+                );
+                report.unmounted.push(record.target.clone());
+            }
+            // Already gone, e.g. detached together with its parent.
+            Err(Errno::INVAL) | Err(Errno::NOENT) => {
+                report.unmounted.push(record.target.clone());
+            }
+            Err(e) => {
+                log::warn!(
+                    "Failed to revert mount on {}: {}",
+                    record.target.display(),
+                    e
+                );
+                report.failed.push(UnmountFailure {
+                    target: record.target.clone(),
+                    error: e.to_string(),
+                });
+            }
+        }
+    }
+
+    report
+}
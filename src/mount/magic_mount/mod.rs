@@ -1,9 +1,9 @@
 // Copyright 2026 https://github.com/Tools-cx-app/meta-magic_mount
 
-mod utils;
+pub mod utils;
 
 use std::{
-    collections::HashSet,
+    collections::BTreeMap,
     fs,
     path::{Path, PathBuf},
     sync::atomic::AtomicU32,
@@ -19,6 +19,7 @@ use rustix::mount::{
 use crate::mount::umount_mgr::send_umountable;
 use crate::{
     mount::{
+        journal::{self, MountKind},
         magic_mount::utils::{clone_symlink, collect_module_files, mount_mirror},
         node::{Node, NodeFileType},
     },
@@ -125,6 +126,9 @@ impl MagicMount {
                 self.work_dir_path.display(),
             )
         })?;
+        if !self.has_tmpfs {
+            journal::record(MountKind::MagicBind, target);
+        }
 
         if let Err(e) = mount_remount(target, MountFlags::RDONLY | MountFlags::BIND, "") {
             log::warn!("make file {} ro: {e:#?}", target.display());
@@ -247,6 +251,7 @@ impl MagicMount {
                     self.path.display()
                 )
             })?;
+            journal::record(MountKind::MagicTmpfs, &self.path);
             if let Err(e) = mount_change(&self.path, MountPropagationFlags::PRIVATE) {
                 log::warn!("make dir {} private: {e:#?}", self.path.display());
             }
@@ -305,14 +310,15 @@ pub fn magic_mount<P>(
     module_dir: &Path,
     mount_source: &str,
     extra_partitions: &[String],
-    need_id: HashSet<String>,
+    scopes: &BTreeMap<String, Vec<PathBuf>>,
+    order: &[String],
     #[cfg(any(target_os = "linux", target_os = "android"))] umount: bool,
     #[cfg(not(any(target_os = "linux", target_os = "android")))] _umount: bool,
 ) -> Result<()>
 where
     P: AsRef<Path>,
 {
-    if let Some(root) = collect_module_files(module_dir, extra_partitions, need_id)? {
+    if let Some(root) = collect_module_files(module_dir, extra_partitions, scopes, order)? {
         log::debug!("collected: {root:?}");
         let tmp_root = tmp_path.as_ref();
         let tmp_dir = tmp_root.join("workdir");
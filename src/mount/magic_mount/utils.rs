@@ -1,7 +1,7 @@
 // Copyright 2026 https://github.com/Tools-cx-app/meta-magic_mount
 
 use std::{
-    collections::HashSet,
+    collections::{BTreeMap, HashSet},
     fs::{self, DirEntry, Metadata, create_dir, create_dir_all, read_link},
     os::unix::fs::{MetadataExt, symlink},
     path::{Path, PathBuf},
@@ -16,7 +16,7 @@ use rustix::{
 use crate::{
     defs::{DISABLE_FILE_NAME, REMOVE_FILE_NAME, SKIP_MOUNT_FILE_NAME},
     mount::node::Node,
-    utils::{lgetfilecon, lsetfilecon, validate_module_id},
+    utils::{lgetfilecon, lsetfilecon},
 };
 
 fn metadata_path<P>(path: P, node: &Node) -> Result<(Metadata, PathBuf)>
@@ -108,7 +108,8 @@ where
 pub fn collect_module_files(
     module_dir: &Path,
     extra_partitions: &[String],
-    need_id: HashSet<String>,
+    scopes: &BTreeMap<String, Vec<PathBuf>>,
+    order: &[String],
 ) -> Result<Option<Node>> {
     let mut root = Node::new_root("");
     let mut system = Node::new_root("system");
@@ -117,66 +118,60 @@ pub fn collect_module_files(
 
     log::debug!("begin collect module files: {}", module_root.display());
 
-    for entry in module_root.read_dir()?.flatten() {
-        if !entry.file_type()?.is_dir() {
+    // The first module to provide a node keeps it, so modules are visited
+    // from the topmost layer down. Ids missing from `order` come last.
+    let ids = order
+        .iter()
+        .filter(|id| scopes.contains_key(*id))
+        .chain(scopes.keys().filter(|id| !order.contains(id)));
+
+    for id in ids {
+        let module_path = module_root.join(id);
+        if !module_path.is_dir() {
             continue;
         }
 
-        let id = entry.file_name().to_str().unwrap().to_string();
         log::debug!("processing new module: {id}");
 
-        if !need_id.contains(&id) {
-            log::debug!("module {id} was blocked.");
-            continue;
-        }
-
-        let prop = entry.path().join("module.prop");
-        if !prop.exists() {
-            log::debug!("skipped module {id}, because not found module.prop");
-            continue;
-        }
-        let string = fs::read_to_string(prop)?;
-        for line in string.lines() {
-            if line.starts_with("id")
-                && let Some((_, value)) = line.split_once('=')
-            {
-                validate_module_id(value)?;
-            }
-        }
+        let module_scopes = &scopes[id];
 
-        if entry.path().join(DISABLE_FILE_NAME).exists()
-            || entry.path().join(REMOVE_FILE_NAME).exists()
-            || entry.path().join(SKIP_MOUNT_FILE_NAME).exists()
+        if module_path.join(DISABLE_FILE_NAME).exists()
+            || module_path.join(REMOVE_FILE_NAME).exists()
+            || module_path.join(SKIP_MOUNT_FILE_NAME).exists()
         {
             log::debug!("skipped module {id}, due to disable/remove/skip_mount");
             continue;
         }
 
-        let mut modified = false;
         let mut partitions = HashSet::new();
         partitions.insert("system".to_string());
         partitions.extend(extra_partitions.iter().cloned());
 
-        for p in &partitions {
-            if entry.path().join(p).is_dir() {
-                modified = true;
-                break;
-            }
-            log::debug!("{id} due not modify {p}");
-        }
+        log::debug!("collecting {}", module_path.display());
 
-        if !modified {
-            continue;
-        }
+        for scope in module_scopes {
+            let mut components = scope.iter();
+            let Some(p) = components.next().map(|p| p.to_string_lossy().to_string()) else {
+                continue;
+            };
 
-        log::debug!("collecting {}", entry.path().display());
+            if !partitions.contains(&p) {
+                log::debug!("{id} due not modify {p}");
+                continue;
+            }
 
-        for p in partitions {
-            if !entry.path().join(&p).exists() {
+            if !module_path.join(&p).exists() {
                 continue;
             }
 
-            has_file.insert(system.collect_module_files(entry.path().join(&p))?);
+            // Other partitions are collected as a child of `system`, which is
+            // moved to the root below when the partition is mounted there.
+            let collected = if p == "system" {
+                system.collect_module_subtree(module_path.join(&p), components.as_path())?
+            } else {
+                system.collect_module_subtree(&module_path, scope)?
+            };
+            has_file.insert(collected);
         }
     }
 
@@ -1,3 +1,4 @@
+pub mod journal;
 pub mod magic_mount;
 pub mod node;
 pub mod overlayfs;
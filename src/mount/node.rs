@@ -2,19 +2,20 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use std::{
-    collections::{HashMap, hash_map::Entry},
+    collections::{BTreeMap, HashMap, hash_map::Entry},
     fmt,
-    fs::{DirEntry, FileType},
+    fs::{self, DirEntry, FileType},
     os::unix::fs::{FileTypeExt, MetadataExt},
     path::{Path, PathBuf},
 };
 
 use anyhow::Result;
 use extattr::lgetxattr;
+use serde::{Serialize, Serializer};
 
 use crate::defs::{REPLACE_DIR_FILE_NAME, REPLACE_DIR_XATTR};
 
-#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+#[derive(PartialEq, Eq, Hash, Clone, Debug, Serialize)]
 pub enum NodeFileType {
     RegularFile,
     Directory,
@@ -36,10 +37,11 @@ impl From<FileType> for NodeFileType {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Node {
     pub name: String,
     pub file_type: NodeFileType,
+    #[serde(serialize_with = "serialize_sorted")]
     pub children: HashMap<String, Self>,
     // the module that owned this node
     pub module_path: Option<PathBuf>,
@@ -47,6 +49,16 @@ pub struct Node {
     pub skip: bool,
 }
 
+fn serialize_sorted<S>(children: &HashMap<String, Node>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    children
+        .iter()
+        .collect::<BTreeMap<_, _>>()
+        .serialize(serializer)
+}
+
 impl fmt::Display for Node {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -83,6 +95,43 @@ impl Node {
         Ok(has_file)
     }
 
+    /// Like `collect_module_files`, but only takes the entry at `relative` below
+    /// `module_dir`, creating the directory nodes leading to it on the way.
+    pub fn collect_module_subtree<P>(&mut self, module_dir: P, relative: &Path) -> Result<bool>
+    where
+        P: AsRef<Path>,
+    {
+        let mut components = relative.iter();
+        let Some(name) = components.next() else {
+            return self.collect_module_files(module_dir);
+        };
+        let rest = components.as_path();
+        let path = module_dir.as_ref().join(name);
+        let name = name.to_string_lossy().to_string();
+
+        let node = match self.children.entry(name.clone()) {
+            Entry::Occupied(o) => Some(o.into_mut()),
+            Entry::Vacant(v) => Self::new_module_path(&name, &path).map(|mut it| {
+                // only part of this directory is collected, it must not replace the rest
+                if !rest.as_os_str().is_empty() {
+                    it.replace = false;
+                }
+                v.insert(it)
+            }),
+        };
+
+        let Some(node) = node else {
+            return Ok(false);
+        };
+
+        if node.file_type == NodeFileType::Directory {
+            let has_file = node.collect_module_subtree(&path, rest)?;
+            Ok(has_file || (rest.as_os_str().is_empty() && node.replace))
+        } else {
+            Ok(rest.as_os_str().is_empty())
+        }
+    }
+
     fn dir_is_replace<P>(path: P) -> bool
     where
         P: AsRef<Path>,
@@ -114,15 +163,21 @@ impl Node {
     where
         S: ToString,
     {
-        if let Ok(metadata) = entry.metadata() {
-            let path = entry.path();
+        Self::new_module_path(name, &entry.path())
+    }
+
+    pub fn new_module_path<S>(name: &S, path: &Path) -> Option<Self>
+    where
+        S: ToString,
+    {
+        if let Ok(metadata) = fs::symlink_metadata(path) {
             let file_type = if metadata.file_type().is_char_device() && metadata.rdev() == 0 {
                 Some(NodeFileType::Whiteout)
             } else {
                 Some(NodeFileType::from(metadata.file_type()))
             };
             if let Some(file_type) = file_type {
-                let replace = file_type == NodeFileType::Directory && Self::dir_is_replace(&path);
+                let replace = file_type == NodeFileType::Directory && Self::dir_is_replace(path);
                 if replace {
                     log::debug!("{} need replace", path.display());
                 }
@@ -130,7 +185,7 @@ impl Node {
                     name: name.to_string(),
                     file_type,
                     children: HashMap::default(),
-                    module_path: Some(path),
+                    module_path: Some(path.to_path_buf()),
                     replace,
                     skip: false,
                 });
@@ -19,12 +19,14 @@ use rustix::{
 
 use crate::{
     defs,
-    mount::{overlayfs::utils::umount_dir, umount_mgr::send_umountable},
+    mount::{
+        journal::{self, MountKind},
+        umount_mgr::send_umountable,
+    },
+    sys::kernel,
     utils::ensure_dir_exists,
 };
 
-const MAX_LAYERS: usize = 64;
-
 fn mount_overlay_core(
     lower_dirs: &[String],
     upperdir: Option<&Path>,
@@ -48,10 +50,20 @@ fn mount_overlay_core(
         .filter(|wd| wd.exists())
         .map(|e| e.display().to_string());
 
+    let caps = kernel::capabilities();
     let result = (|| {
+        if !caps.fsopen {
+            return Err(rustix::io::Errno::NOSYS);
+        }
         let fs = fsopen("overlay", FsOpenFlags::FSOPEN_CLOEXEC)?;
         let fs = fs.as_fd();
-        fsconfig_set_string(fs, "lowerdir", &lowerdir_config)?;
+        if caps.overlay_lowerdir_plus {
+            for lower in lower_dirs {
+                fsconfig_set_string(fs, "lowerdir+", lower)?;
+            }
+        } else {
+            fsconfig_set_string(fs, "lowerdir", &lowerdir_config)?;
+        }
         if let (Some(upperdir), Some(workdir)) = (&upperdir_s, &workdir_s) {
             fsconfig_set_string(fs, "upperdir", upperdir)?;
             fsconfig_set_string(fs, "workdir", workdir)?;
@@ -69,8 +81,15 @@ fn mount_overlay_core(
     })();
 
     if let Err(e) = result {
-        log::warn!("fsopen mount failed: {:#}, fallback to mount", e);
-        let safe_lower = lowerdir_config.replace(',', "\\,");
+        if caps.fsopen {
+            log::warn!("fsopen mount failed: {:#}, fallback to mount", e);
+        }
+        // The legacy option string is bounded by a page, so layers that
+        // lowerdir+ would have taken in one go are staged in chunks first.
+        let mut lower_dirs = lower_dirs.to_vec();
+        stage_layers(&mut lower_dirs, kernel::LEGACY_MAX_LAYERS, mount_source)?;
+
+        let safe_lower = lower_dirs.join(":").replace(',', "\\,");
         let mut data = format!("lowerdir={safe_lower}");
 
         if let (Some(upperdir), Some(workdir)) = (upperdir_s, workdir_s) {
@@ -91,20 +110,12 @@ fn mount_overlay_core(
     Ok(())
 }
 
-pub fn mount_overlayfs(
-    lower_dirs: &[String],
-    lowest: &str,
-    upperdir: Option<PathBuf>,
-    workdir: Option<PathBuf>,
-    dest: impl AsRef<Path>,
-    mount_source: &str,
-) -> Result<()> {
-    let mut current_layers: Vec<String> = lower_dirs.to_vec();
-    current_layers.push(lowest.to_string());
-
-    while current_layers.len() > MAX_LAYERS {
-        let split_idx = current_layers.len().saturating_sub(MAX_LAYERS - 1);
-        let bottom_chunk: Vec<String> = current_layers.drain(split_idx..).collect();
+// Collapses the bottom layers into staged overlays until the rest fits into a
+// single mount.
+fn stage_layers(layers: &mut Vec<String>, max_layers: usize, mount_source: &str) -> Result<()> {
+    while layers.len() > max_layers {
+        let split_idx = layers.len().saturating_sub(max_layers - 1);
+        let bottom_chunk: Vec<String> = layers.drain(split_idx..).collect();
 
         let timestamp = SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -115,19 +126,47 @@ pub fn mount_overlayfs(
         ensure_dir_exists(&staging_dir)?;
 
         mount_overlay_core(&bottom_chunk, None, None, &staging_dir, mount_source)?;
+        journal::record(MountKind::Staging, &staging_dir);
 
         let _ = send_umountable(&staging_dir);
 
-        current_layers.push(staging_dir.to_string_lossy().to_string());
+        layers.push(staging_dir.to_string_lossy().to_string());
     }
 
-    mount_overlay_core(
-        &current_layers,
-        upperdir.as_deref(),
-        workdir.as_deref(),
-        dest.as_ref(),
-        mount_source,
-    )
+    Ok(())
+}
+
+pub fn mount_overlayfs(
+    lower_dirs: &[String],
+    lowest: &str,
+    upperdir: Option<PathBuf>,
+    workdir: Option<PathBuf>,
+    dest: impl AsRef<Path>,
+    mount_source: &str,
+) -> Result<()> {
+    let mark = journal::mark();
+    let max_layers = kernel::capabilities().overlay_max_layers;
+    let mut current_layers: Vec<String> = lower_dirs.to_vec();
+    current_layers.push(lowest.to_string());
+
+    let result = (|| -> Result<()> {
+        stage_layers(&mut current_layers, max_layers, mount_source)?;
+
+        mount_overlay_core(
+            &current_layers,
+            upperdir.as_deref(),
+            workdir.as_deref(),
+            dest.as_ref(),
+            mount_source,
+        )?;
+        journal::record(MountKind::Overlay, dest.as_ref());
+        Ok(())
+    })();
+
+    if result.is_err() {
+        journal::rollback(mark);
+    }
+    result
 }
 
 pub fn bind_mount(from: impl AsRef<Path>, to: impl AsRef<Path>) -> Result<()> {
@@ -137,14 +176,17 @@ pub fn bind_mount(from: impl AsRef<Path>, to: impl AsRef<Path>) -> Result<()> {
         to.as_ref().display()
     );
     use rustix::mount::{OpenTreeFlags, open_tree};
-    match open_tree(
-        CWD,
-        from.as_ref(),
-        OpenTreeFlags::OPEN_TREE_CLOEXEC
-            | OpenTreeFlags::OPEN_TREE_CLONE
-            | OpenTreeFlags::AT_RECURSIVE,
-    ) {
-        Result::Ok(tree) => {
+    let tree = kernel::capabilities().open_tree.then(|| {
+        open_tree(
+            CWD,
+            from.as_ref(),
+            OpenTreeFlags::OPEN_TREE_CLOEXEC
+                | OpenTreeFlags::OPEN_TREE_CLONE
+                | OpenTreeFlags::AT_RECURSIVE,
+        )
+    });
+    match tree {
+        Some(Result::Ok(tree)) => {
             move_mount(
                 tree.as_fd(),
                 "",
@@ -163,6 +205,7 @@ pub fn bind_mount(from: impl AsRef<Path>, to: impl AsRef<Path>) -> Result<()> {
             )?;
         }
     }
+    journal::record(MountKind::Bind, to.as_ref());
     Ok(())
 }
 
@@ -218,6 +261,7 @@ pub fn mount_overlay(
     mount_source: &str,
 ) -> Result<()> {
     log::info!("mount overlay for {}", root);
+    let mark = journal::mark();
     std::env::set_current_dir(root).with_context(|| format!("failed to chdir to {root}"))?;
     let stock_root = ".";
 
@@ -258,7 +302,9 @@ pub fn mount_overlay(
                 mount_point,
                 e
             );
-            umount_dir(root).with_context(|| format!("failed to revert {root}"))?;
+            if !journal::rollback(mark).failed.is_empty() {
+                return Err(e).with_context(|| format!("failed to revert {root}"));
+            }
             bail!(e);
         }
     }
@@ -0,0 +1,109 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{fs, os::fd::AsFd, path::Path, sync::OnceLock};
+
+use extattr::{Flags as XattrFlags, lsetxattr};
+use loopdev::LoopControl;
+use rustix::{
+    fs::CWD,
+    mount::{
+        FsOpenFlags, OpenTreeFlags, UnmountFlags, fsconfig_set_string, fsopen, open_tree, unmount,
+    },
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{defs, utils};
+
+pub const LEGACY_MAX_LAYERS: usize = 64;
+const LOWERDIR_PLUS_MAX_LAYERS: usize = 500;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct KernelCapabilities {
+    pub kernel_release: String,
+    pub overlay: bool,
+    pub erofs: bool,
+    pub tmpfs_xattr: bool,
+    pub fsopen: bool,
+    pub open_tree: bool,
+    pub loop_control: bool,
+    pub overlay_lowerdir_plus: bool,
+    pub overlay_max_layers: usize,
+}
+
+static CAPABILITIES: OnceLock<KernelCapabilities> = OnceLock::new();
+
+pub fn capabilities() -> &'static KernelCapabilities {
+    CAPABILITIES.get_or_init(KernelCapabilities::probe)
+}
+
+impl KernelCapabilities {
+    fn probe() -> Self {
+        let filesystems = fs::read_to_string("/proc/filesystems").unwrap_or_default();
+        let has_fs = |name: &str| {
+            filesystems
+                .lines()
+                .any(|line| line.split_whitespace().last() == Some(name))
+        };
+
+        let overlay = has_fs("overlay");
+        let fsopen = fsopen("tmpfs", FsOpenFlags::FSOPEN_CLOEXEC).is_ok();
+        let overlay_lowerdir_plus = overlay && fsopen && probe_lowerdir_plus();
+
+        let caps = Self {
+            kernel_release: fs::read_to_string("/proc/sys/kernel/osrelease")
+                .map(|s| s.trim().to_string())
+                .unwrap_or_default(),
+            overlay,
+            erofs: has_fs("erofs"),
+            tmpfs_xattr: probe_tmpfs_xattr(),
+            fsopen,
+            open_tree: open_tree(CWD, "/", OpenTreeFlags::OPEN_TREE_CLOEXEC).is_ok(),
+            loop_control: LoopControl::open().is_ok(),
+            overlay_lowerdir_plus,
+            overlay_max_layers: if overlay_lowerdir_plus {
+                LOWERDIR_PLUS_MAX_LAYERS
+            } else {
+                LEGACY_MAX_LAYERS
+            },
+        };
+
+        log::debug!("Kernel capabilities: {:?}", caps);
+        caps
+    }
+}
+
+// Kernels before 6.8 reject the `lowerdir+` key while configuring the context.
+fn probe_lowerdir_plus() -> bool {
+    let Ok(fs) = fsopen("overlay", FsOpenFlags::FSOPEN_CLOEXEC) else {
+        return false;
+    };
+
+    fsconfig_set_string(fs.as_fd(), "lowerdir+", "/").is_ok()
+}
+
+// /proc/config.gz is frequently missing, so mount a scratch tmpfs and try to
+// set a trusted xattr on it, which is exactly what the tmpfs backend needs.
+fn probe_tmpfs_xattr() -> bool {
+    let probe_dir = Path::new(defs::RUN_DIR).join("xattr_probe");
+
+    if crate::sys::mount::mount_tmpfs(&probe_dir, "xattr_probe").is_err() {
+        log::debug!("Cannot mount probe tmpfs, falling back to kernel config");
+        return utils::is_overlay_xattr_supported().unwrap_or(false);
+    }
+
+    let supported = lsetxattr(
+        &probe_dir,
+        "trusted.overlay.opaque",
+        b"y",
+        XattrFlags::empty(),
+    )
+    .is_ok();
+
+    if let Err(e) = unmount(&probe_dir, UnmountFlags::DETACH) {
+        log::warn!("Failed to unmount xattr probe: {}", e);
+    }
+    let _ = fs::remove_dir(&probe_dir);
+
+    supported
+}
@@ -1,3 +1,5 @@
+pub mod kernel;
 pub mod mount;
 pub mod nuke;
 pub mod poaceae;
+pub mod props;
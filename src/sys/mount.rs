@@ -36,6 +36,19 @@ pub fn is_mounted<P: AsRef<Path>>(path: P) -> bool {
     false
 }
 
+pub fn mount_source<P: AsRef<Path>>(path: P) -> Option<String> {
+    let path = path.as_ref();
+
+    Process::myself()
+        .ok()?
+        .mountinfo()
+        .ok()?
+        .into_iter()
+        .rev()
+        .find(|m| m.mount_point == path)
+        .and_then(|m| m.mount_source)
+}
+
 pub fn mount_tmpfs(target: &Path, source: &str) -> Result<()> {
     ensure_dir_exists(target)?;
     mount(
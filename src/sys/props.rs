@@ -0,0 +1,92 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+    process::Command,
+    sync::{LazyLock, Mutex, OnceLock},
+};
+
+use anyhow::{Context, Result};
+
+pub const SDK_PROP: &str = "ro.build.version.sdk";
+pub const DEVICE_PROP: &str = "ro.product.device";
+/// Pseudo properties for values that do not live in the property service.
+pub const KERNEL_RELEASE_PROP: &str = "kernel.osrelease";
+pub const SELINUX_ENFORCE_PROP: &str = "selinux.enforce";
+
+pub trait PropertySource: Send + Sync {
+    fn get(&self, key: &str) -> Option<String>;
+}
+
+/// Reads Android properties through `getprop`, the kernel release from
+/// procfs and the SELinux mode from selinuxfs. Lookups are cached.
+#[derive(Default)]
+pub struct SystemProperties {
+    cache: Mutex<HashMap<String, Option<String>>>,
+}
+
+impl SystemProperties {
+    fn lookup(key: &str) -> Option<String> {
+        let value = match key {
+            KERNEL_RELEASE_PROP => fs::read_to_string("/proc/sys/kernel/osrelease").ok()?,
+            SELINUX_ENFORCE_PROP => fs::read_to_string("/sys/fs/selinux/enforce").ok()?,
+            _ => {
+                let output = Command::new("getprop").arg(key).output().ok()?;
+                String::from_utf8_lossy(&output.stdout).into_owned()
+            }
+        };
+
+        let value = value.trim();
+        (!value.is_empty()).then(|| value.to_string())
+    }
+}
+
+impl PropertySource for SystemProperties {
+    fn get(&self, key: &str) -> Option<String> {
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        cache
+            .entry(key.to_string())
+            .or_insert_with(|| Self::lookup(key))
+            .clone()
+    }
+}
+
+/// Fixed property set, used to evaluate conditions off-device.
+pub struct StaticProperties(pub HashMap<String, String>);
+
+impl StaticProperties {
+    /// Loads a JSON object such as
+    /// `{"ro.build.version.sdk": "34", "kernel.osrelease": "5.15.110"}`.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("failed to read properties from {}", path.display()))?;
+        let props = serde_json::from_str(&content)
+            .with_context(|| format!("failed to parse properties from {}", path.display()))?;
+        Ok(Self(props))
+    }
+}
+
+impl PropertySource for StaticProperties {
+    fn get(&self, key: &str) -> Option<String> {
+        self.0.get(key).cloned()
+    }
+}
+
+static SYSTEM: LazyLock<SystemProperties> = LazyLock::new(SystemProperties::default);
+static OVERRIDE: OnceLock<Box<dyn PropertySource>> = OnceLock::new();
+
+/// Replaces the system properties for the rest of the process. Only the
+/// first call has an effect.
+pub fn install(source: Box<dyn PropertySource>) {
+    let _ = OVERRIDE.set(source);
+}
+
+pub fn source() -> &'static dyn PropertySource {
+    match OVERRIDE.get() {
+        Some(source) => source.as_ref(),
+        None => &*SYSTEM,
+    }
+}
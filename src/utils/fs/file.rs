@@ -1,5 +1,4 @@
 use std::{
-    collections::HashSet,
     ffi::CString,
     fs::{self, File, OpenOptions},
     io::Write,
@@ -14,9 +13,14 @@ use walkdir::WalkDir;
 
 use super::xattr::internal_copy_extended_attributes;
 
+/// Writes through a temp file that is fsynced and renamed over `path`, then
+/// fsyncs the directory so the rename survives a power loss.
 pub fn atomic_write<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, content: C) -> Result<()> {
     let path = path.as_ref();
-    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let dir = path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
 
     let now = SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -32,15 +36,24 @@ pub fn atomic_write<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, content: C) -> Resu
             .create_new(true)
             .open(&temp_file)?;
         file.write_all(content.as_ref())?;
+        if let Err(e) = file.sync_all() {
+            let _ = fs::remove_file(&temp_file);
+            return Err(e).context("atomic_write failed to sync temp file");
+        }
     }
 
     if let Err(_e) = fs::rename(&temp_file, path) {
-        if let Err(copy_err) = fs::copy(&temp_file, path) {
-            let _ = fs::remove_file(&temp_file);
-            return Err(copy_err).context("atomic_write copy fallback failed");
-        }
+        let copied = fs::copy(&temp_file, path)
+            .and_then(|_| File::open(path))
+            .and_then(|file| file.sync_all());
         let _ = fs::remove_file(&temp_file);
+        copied.context("atomic_write copy fallback failed")?;
     }
+
+    File::open(dir)
+        .and_then(|dir| dir.sync_all())
+        .with_context(|| format!("atomic_write failed to sync {}", dir.display()))?;
+
     Ok(())
 }
 
@@ -78,77 +91,31 @@ fn make_device_node(path: &Path, mode: u32, rdev: u64) -> Result<()> {
     Ok(())
 }
 
-fn native_cp_r(
-    src: &Path,
-    dst: &Path,
-    relative: &Path,
-    _repair: bool,
-    visited: &mut HashSet<(u64, u64)>,
-) -> Result<()> {
-    if !dst.exists() {
-        if src.is_dir() {
-            fs::create_dir_all(dst)?;
+/// Copies a single non-directory entry with its xattrs, replacing `dst`.
+pub fn copy_entry(src: &Path, dst: &Path, metadata: &fs::Metadata) -> Result<()> {
+    let ft = metadata.file_type();
+
+    if ft.is_symlink() {
+        if dst.symlink_metadata().is_ok() {
+            fs::remove_file(dst)?;
         }
-        if let Ok(src_meta) = src.metadata() {
-            let _ = fs::set_permissions(dst, src_meta.permissions());
+        let link_target = fs::read_link(src)?;
+        symlink(&link_target, dst)?;
+    } else if ft.is_char_device() || ft.is_block_device() || ft.is_fifo() {
+        if dst.symlink_metadata().is_ok() {
+            fs::remove_file(dst)?;
         }
-        let _ = internal_copy_extended_attributes(src, dst);
+        let mode = metadata.permissions().mode();
+        let rdev = metadata.rdev();
+        make_device_node(dst, mode, rdev)?;
+    } else {
+        reflink_or_copy(src, dst)?;
     }
 
-    for entry in fs::read_dir(src)? {
-        let entry = entry?;
-        let src_path = entry.path();
-        let file_name = entry.file_name();
-        let dst_path = dst.join(&file_name);
-        let next_relative = relative.join(&file_name);
-
-        let metadata = entry.metadata()?;
-        let ft = metadata.file_type();
-        let dev = metadata.dev();
-        let ino = metadata.ino();
-
-        if ft.is_dir() {
-            if !visited.insert((dev, ino)) {
-                continue;
-            }
-            native_cp_r(&src_path, &dst_path, &next_relative, _repair, visited)?;
-        } else if ft.is_symlink() {
-            if dst_path.exists() {
-                fs::remove_file(&dst_path)?;
-            }
-            let link_target = fs::read_link(&src_path)?;
-            symlink(&link_target, &dst_path)?;
-        } else if ft.is_char_device() || ft.is_block_device() || ft.is_fifo() {
-            if dst_path.exists() {
-                fs::remove_file(&dst_path)?;
-            }
-            let mode = metadata.permissions().mode();
-            let rdev = metadata.rdev();
-            make_device_node(&dst_path, mode, rdev)?;
-        } else {
-            reflink_or_copy(&src_path, &dst_path)?;
-        }
-
-        let _ = internal_copy_extended_attributes(&src_path, &dst_path);
-    }
+    let _ = internal_copy_extended_attributes(src, dst);
     Ok(())
 }
 
-pub fn sync_dir(src: &Path, dst: &Path, repair_context: bool) -> Result<()> {
-    if !src.exists() {
-        return Ok(());
-    }
-    ensure_dir_exists(dst)?;
-    let mut visited = HashSet::new();
-    native_cp_r(src, dst, Path::new(""), repair_context, &mut visited).with_context(|| {
-        format!(
-            "Failed to natively sync {} to {}",
-            src.display(),
-            dst.display()
-        )
-    })
-}
-
 pub fn prune_empty_dirs<P: AsRef<Path>>(root: P) -> Result<()> {
     let root = root.as_ref();
     if !root.exists() {
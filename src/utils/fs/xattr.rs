@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::{collections::BTreeMap, path::Path};
 #[cfg(any(target_os = "linux", target_os = "android"))]
 use std::{fs, io::Read, os::unix::ffi::OsStrExt};
 
@@ -91,6 +91,26 @@ pub fn lgetfilecon<P: AsRef<Path>>(_path: P) -> Result<String> {
     unimplemented!();
 }
 
+/// All extended attributes of `path`, without following symlinks.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn lgetxattrs<P: AsRef<Path>>(path: P) -> BTreeMap<String, Vec<u8>> {
+    let path = path.as_ref();
+    let mut xattrs = BTreeMap::new();
+    if let Ok(names) = llistxattr(path) {
+        for name in names {
+            if let Ok(value) = lgetxattr(path, &name) {
+                xattrs.insert(String::from_utf8_lossy(name.as_bytes()).to_string(), value);
+            }
+        }
+    }
+    xattrs
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub fn lgetxattrs<P: AsRef<Path>>(_path: P) -> BTreeMap<String, Vec<u8>> {
+    BTreeMap::new()
+}
+
 #[cfg(any(target_os = "linux", target_os = "android"))]
 pub fn is_overlay_xattr_supported() -> Result<bool> {
     use flate2::read::GzDecoder;
@@ -0,0 +1,155 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+// Path patterns are matched component by component: `*` and `?` never cross a
+// `/`, while a `**` component spans any number of components.
+
+pub fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?'])
+}
+
+fn components(path: &str) -> Vec<&str> {
+    path.split('/')
+        .filter(|c| !c.is_empty() && *c != ".")
+        .collect()
+}
+
+fn match_component(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            n = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+fn match_components(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|i| match_components(rest, &path[i..])),
+        Some((first, rest)) => path.split_first().is_some_and(|(name, tail)| {
+            match_component(first, name) && match_components(rest, tail)
+        }),
+    }
+}
+
+fn may_extend(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => false,
+        Some((&"**", rest)) => {
+            rest.is_empty() || (0..=path.len()).any(|i| may_extend(rest, &path[i..]))
+        }
+        Some((first, rest)) => match path.split_first() {
+            None => true,
+            Some((name, tail)) => match_component(first, name) && may_extend(rest, tail),
+        },
+    }
+}
+
+/// Matches `path` itself or any of its ancestors, so a pattern covers the
+/// whole subtree below whatever it names.
+pub fn glob_match_prefix(pattern: &str, path: &str) -> bool {
+    let pattern = components(pattern);
+    let path = components(path);
+
+    !pattern.is_empty() && (1..=path.len()).any(|len| match_components(&pattern, &path[..len]))
+}
+
+/// Whether `pattern` can match some path strictly below `path`.
+pub fn glob_may_match_below(pattern: &str, path: &str) -> bool {
+    may_extend(&components(pattern), &components(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn star_and_question_mark_stay_within_a_component() {
+        assert!(match_component("*.apk", "Settings.apk"));
+        assert!(match_component("lib*.so", "libfoo.so"));
+        assert!(match_component("lib*", "lib"));
+        assert!(match_component("?oo", "foo"));
+        assert!(match_component("f?o", "foo"));
+        assert!(match_component("fo?", "foo"));
+        assert!(match_component("*a*b*", "xxaxxbxx"));
+        assert!(!match_component("?oo", "oo"));
+        assert!(!match_component("*.apk", "Settings.odex"));
+        assert!(!match_component("a*b", "acbd"));
+
+        assert!(glob_match_prefix("system/*/Foo", "system/app/Foo"));
+        assert!(!glob_match_prefix("system/*", "vendor/app"));
+        assert!(!glob_match_prefix("system/*.so", "system/lib"));
+    }
+
+    #[test]
+    fn double_star_spans_components() {
+        assert!(glob_match_prefix("**/Foo.apk", "Foo.apk"));
+        assert!(glob_match_prefix("**/Foo.apk", "system/app/Foo/Foo.apk"));
+        assert!(glob_match_prefix("system/**/Foo.apk", "system/Foo.apk"));
+        assert!(glob_match_prefix(
+            "system/**/Foo.apk",
+            "system/app/Foo/Foo.apk"
+        ));
+        assert!(glob_match_prefix("system/**", "system/app/Foo"));
+        assert!(!glob_match_prefix(
+            "system/**/Foo.apk",
+            "vendor/app/Foo.apk"
+        ));
+        assert!(!glob_match_prefix("**/Foo.apk", "system/app/Bar.apk"));
+    }
+
+    #[test]
+    fn empty_components_are_ignored() {
+        assert!(glob_match_prefix("system//app", "system/app"));
+        assert!(glob_match_prefix("/system/app/", "system/app"));
+        assert!(glob_match_prefix("system/app", "/system//app/"));
+        assert!(glob_match_prefix("./system/app", "system/./app"));
+        assert!(!glob_match_prefix("", "system"));
+        assert!(!glob_match_prefix("//", "system"));
+        assert!(!glob_match_prefix("system", ""));
+    }
+
+    #[test]
+    fn prefix_match_covers_the_subtree() {
+        assert!(glob_match_prefix("system/app", "system/app"));
+        assert!(glob_match_prefix("system/app", "system/app/Foo/Foo.apk"));
+        assert!(glob_match_prefix("system/app/*", "system/app/Foo/Foo.apk"));
+        assert!(!glob_match_prefix("system/app", "system"));
+        assert!(!glob_match_prefix("system/app", "system/apps"));
+        assert!(!glob_match_prefix("system/app/Foo", "system/app"));
+    }
+
+    #[test]
+    fn may_match_below() {
+        assert!(glob_may_match_below("system/app/Foo", "system"));
+        assert!(glob_may_match_below("system/*/Foo", "system/app"));
+        assert!(glob_may_match_below("**/Foo.apk", "system/app"));
+        assert!(glob_may_match_below("system/**", "system/app"));
+        assert!(glob_may_match_below("system/**/lib/*.so", "system"));
+        assert!(glob_may_match_below("system/app", ""));
+
+        assert!(!glob_may_match_below("system/app", "system/app"));
+        assert!(!glob_may_match_below("system/app", "system/app/Foo"));
+        assert!(!glob_may_match_below("system/app", "vendor"));
+        assert!(!glob_may_match_below("system/*/Foo", "system/app/Bar"));
+        assert!(!glob_may_match_below("", "system"));
+    }
+}
@@ -0,0 +1,84 @@
+use anyhow::{Result, bail};
+
+fn hex_value(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+pub fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Decodes a hex string, ignoring surrounding whitespace.
+pub fn decode_hex(input: &str) -> Result<Vec<u8>> {
+    let input = input.trim().as_bytes();
+
+    if !input.len().is_multiple_of(2) {
+        bail!("hex input has odd length {}", input.len());
+    }
+
+    input
+        .chunks_exact(2)
+        .enumerate()
+        .map(|(i, pair)| match (hex_value(pair[0]), hex_value(pair[1])) {
+            (Some(high), Some(low)) => Ok((high << 4) | low),
+            _ => {
+                let offset = if hex_value(pair[0]).is_none() {
+                    2 * i
+                } else {
+                    2 * i + 1
+                };
+                bail!(
+                    "invalid hex character '{}' at offset {}",
+                    input[offset].escape_ascii(),
+                    offset
+                )
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let bytes = b"{\"a\":[1,2]}\n\x00\xff";
+
+        assert_eq!(decode_hex(&encode_hex(bytes)).unwrap(), bytes);
+        assert_eq!(decode_hex("DEADbeef").unwrap(), [0xde, 0xad, 0xbe, 0xef]);
+        assert!(decode_hex("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn surrounding_whitespace_is_ignored() {
+        assert_eq!(decode_hex("  7b7d\n").unwrap(), b"{}");
+        assert_eq!(decode_hex("\t\r\n").unwrap(), b"");
+        assert!(decode_hex("7b 7d").is_err());
+    }
+
+    #[test]
+    fn odd_length_is_rejected() {
+        let err = decode_hex("7b7").unwrap_err().to_string();
+        assert!(err.contains("odd length 3"), "{}", err);
+
+        assert!(decode_hex(" 7 ").is_err());
+    }
+
+    #[test]
+    fn non_hex_characters_are_reported_with_offset() {
+        let err = decode_hex("7bzz").unwrap_err().to_string();
+        assert!(err.contains("'z' at offset 2"), "{}", err);
+
+        let err = decode_hex("7g").unwrap_err().to_string();
+        assert!(err.contains("'g' at offset 1"), "{}", err);
+
+        let err = decode_hex("0x7b").unwrap_err().to_string();
+        assert!(err.contains("'x' at offset 1"), "{}", err);
+    }
+}
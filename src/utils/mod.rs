@@ -1,11 +1,14 @@
 pub mod fs;
+pub mod glob;
+pub mod hash;
+pub mod hex;
 pub mod log;
 pub mod process;
 pub mod validation;
 
 use std::path::{Path, PathBuf};
 
-pub use self::{fs::*, log::*, process::*, validation::*};
+pub use self::{fs::*, glob::*, hash::*, hex::*, log::*, process::*, validation::*};
 
 pub fn get_mnt() -> PathBuf {
     let mut name = String::new();
@@ -1,5 +1,5 @@
 use std::{
-    path::Path,
+    path::{Path, PathBuf},
     sync::{
         OnceLock,
         atomic::{AtomicBool, Ordering},
@@ -47,6 +47,14 @@ pub fn extract_module_id(path: &Path) -> Option<String> {
         .map(|s| s.to_string_lossy().to_string())
 }
 
+pub fn split_module_path(path: &Path) -> Option<(String, PathBuf)> {
+    let root = path.ancestors().find(|p| p.join("module.prop").exists())?;
+    let id = root.file_name()?.to_string_lossy().to_string();
+    let relative = path.strip_prefix(root).ok()?.to_path_buf();
+
+    Some((id, relative))
+}
+
 pub fn check_zygisksu_enforce_status() -> bool {
     std::fs::read_to_string(defs::ZYGISKSU_DENYLIST_FILE)
         .map(|s| s.trim() != "0")
@@ -7,18 +7,33 @@ import type {
   StorageStatus,
   SystemInfo,
   ModuleRules,
+  ValidationReport,
+  ConfigSnapshot,
+  PatchResult,
 } from "./types";
 
 const delay = (ms: number) => new Promise((resolve) => setTimeout(resolve, ms));
 
 export const MockAPI = {
-  async loadConfig(): Promise<AppConfig> {
+  async loadConfig(): Promise<ConfigSnapshot> {
     await delay(300);
-    return { ...DEFAULT_CONFIG };
+    return {
+      config: { ...DEFAULT_CONFIG },
+      revision: "0000000000000000",
+      file: {},
+    };
+  },
+  async validateConfig(_config: AppConfig): Promise<ValidationReport> {
+    await delay(200);
+    return { valid: true, issues: [] };
   },
-  async saveConfig(config: AppConfig): Promise<void> {
+  async patchConfig(
+    patch: Record<string, unknown>,
+    _revision: string | null,
+  ): Promise<PatchResult> {
     await delay(500);
-    console.log("[Mock] Config saved:", config);
+    console.log("[Mock] Config patched:", patch);
+    return { revision: "0000000000000000", shadowed: [] };
   },
   async resetConfig(): Promise<void> {
     await delay(500);
@@ -33,11 +48,11 @@ export const MockAPI = {
         version: "1.0.0",
         author: "Developer",
         description: "This is a Mock Module for Testing",
-        mode: "Magic",
+        mode: "magic",
         is_mounted: true,
         rules: {
-          default_mode: "Magic",
-          paths: { "System/Fonts": "Overlay" },
+          default_mode: "magic",
+          paths: { "system/fonts": "overlay" },
         },
       },
       {
@@ -49,7 +64,7 @@ export const MockAPI = {
         mode: "Auto",
         is_mounted: true,
         rules: {
-          default_mode: "Overlay",
+          default_mode: "overlay",
           paths: {},
         },
       },
@@ -59,10 +74,10 @@ export const MockAPI = {
         version: "0.1",
         author: "Tester",
         description: "This Module is Not Mounted",
-        mode: "Ignore",
+        mode: "ignore",
         is_mounted: false,
         rules: {
-          default_mode: "Ignore",
+          default_mode: "ignore",
           paths: {},
         },
       },
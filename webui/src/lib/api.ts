@@ -8,6 +8,9 @@ import type {
   SystemInfo,
   DeviceInfo,
   ModuleRules,
+  ValidationReport,
+  ConfigSnapshot,
+  PatchResult,
 } from "./types";
 
 interface KsuExecResult {
@@ -31,28 +34,19 @@ try {
 
 const shouldUseMock = import.meta.env.DEV || !ksuExec;
 
-function stringToHex(str: string): string {
-  let bytes: Uint8Array;
-  if (typeof TextEncoder !== "undefined") {
-    const encoder = new TextEncoder();
-    bytes = encoder.encode(str);
-  } else {
-    bytes = new Uint8Array(str.length);
-    for (let i = 0; i < str.length; i++) {
-      bytes[i] = str.charCodeAt(i) & 0xff;
-    }
-  }
-  let hex = "";
-  for (let i = 0; i < bytes.length; i++) {
-    const h = bytes[i].toString(16);
-    hex += h.length === 1 ? "0" + h : h;
-  }
-  return hex;
+// Payloads are fed on stdin through a quoted heredoc, which keeps them out of
+// argv (ARG_MAX, `ps`) and needs no escaping since JSON is a single line.
+function withPayload(cmd: string, payload: unknown): string {
+  return `${cmd} --stdin <<'HM_PAYLOAD'\n${JSON.stringify(payload)}\nHM_PAYLOAD`;
 }
 
 interface AppAPI {
-  loadConfig: () => Promise<AppConfig>;
-  saveConfig: (config: AppConfig) => Promise<void>;
+  loadConfig: () => Promise<ConfigSnapshot>;
+  validateConfig: (config: AppConfig) => Promise<ValidationReport>;
+  patchConfig: (
+    patch: Record<string, unknown>,
+    revision: string | null,
+  ) => Promise<PatchResult>;
   resetConfig: () => Promise<void>;
   scanModules: (path?: string) => Promise<Module[]>;
   saveModules: (modules: Module[]) => Promise<void>;
@@ -67,25 +61,41 @@ interface AppAPI {
 }
 
 const RealAPI: AppAPI = {
-  loadConfig: async (): Promise<AppConfig> => {
-    if (!ksuExec) return DEFAULT_CONFIG;
-    const cmd = `${PATHS.BINARY} show-config`;
+  loadConfig: async (): Promise<ConfigSnapshot> => {
+    if (!ksuExec) return { config: DEFAULT_CONFIG, revision: null, file: {} };
+    const cmd = `${PATHS.BINARY} show-config --sources`;
     try {
       const { errno, stdout } = await ksuExec(cmd);
       if (errno === 0 && stdout) {
-        const loaded = JSON.parse(stdout);
-        return { ...DEFAULT_CONFIG, ...loaded };
+        const { config, revision, file } = JSON.parse(stdout);
+        return { config: { ...DEFAULT_CONFIG, ...config }, revision, file };
       }
     } catch {}
-    return DEFAULT_CONFIG;
+    return { config: DEFAULT_CONFIG, revision: null, file: {} };
   },
-  saveConfig: async (config: AppConfig): Promise<void> => {
+  validateConfig: async (config: AppConfig): Promise<ValidationReport> => {
     if (!ksuExec) throw new Error("No KSU Environment");
-    const jsonStr = JSON.stringify(config);
-    const hexPayload = stringToHex(jsonStr);
-    const cmd = `${PATHS.BINARY} save-config --payload ${hexPayload}`;
-    const { errno, stderr } = await ksuExec(cmd);
+    const cmd = withPayload(`${PATHS.BINARY} validate-config`, config);
+    const { stdout, stderr } = await ksuExec(cmd);
+    try {
+      return JSON.parse(stdout);
+    } catch {
+      throw new Error(`Failed to Validate Config: ${stderr}`);
+    }
+  },
+  patchConfig: async (
+    patch: Record<string, unknown>,
+    revision: string | null,
+  ): Promise<PatchResult> => {
+    if (!ksuExec) throw new Error("No KSU Environment");
+    const revisionArg = revision ? ` --revision ${revision}` : "";
+    const cmd = withPayload(
+      `${PATHS.BINARY} patch-config${revisionArg}`,
+      patch,
+    );
+    const { errno, stdout, stderr } = await ksuExec(cmd);
     if (errno !== 0) throw new Error(`Failed to Save Config: ${stderr}`);
+    return JSON.parse(stdout);
   },
   resetConfig: async (): Promise<void> => {
     if (!ksuExec) throw new Error("No KSU environment");
@@ -109,7 +119,7 @@ const RealAPI: AppAPI = {
     if (!ksuExec) return "";
     try {
       const { errno, stdout } = await ksuExec(
-        `cat "${DEFAULT_CONFIG.logfile}"`,
+        `cat "${PATHS.DAEMON_LOG}"`,
       );
       if (errno === 0 && stdout) return stdout;
     } catch (e) {}
@@ -121,9 +131,10 @@ const RealAPI: AppAPI = {
     rules: ModuleRules,
   ): Promise<void> => {
     if (!ksuExec) throw new Error("No KSU environment");
-    const jsonStr = JSON.stringify(rules);
-    const hexPayload = stringToHex(jsonStr);
-    const cmd = `${PATHS.BINARY} save-module-rules --module "${moduleId}" --payload ${hexPayload}`;
+    const cmd = withPayload(
+      `${PATHS.BINARY} save-module-rules --module "${moduleId}"`,
+      rules,
+    );
     const { errno, stderr } = await ksuExec(cmd);
     if (errno !== 0) throw new Error(`Failed to save rules: ${stderr}`);
   },
@@ -9,15 +9,16 @@ import type { AppConfig } from "./types";
 export const DEFAULT_CONFIG: AppConfig = {
   moduledir: "/data/adb/modules",
   mountsource: "KSU",
-  logfile: RUST_PATHS.DAEMON_LOG || "/data/adb/Hybrid-Mount/daemon.log",
   partitions: [],
   disable_umount: false,
   allow_umount_coexistence: false,
   overlay_mode: "tmpfs",
+  default_mode: "overlay",
 };
 
 export const PATHS = {
   ...RUST_PATHS,
+  DAEMON_LOG: RUST_PATHS.DAEMON_LOG || "/data/adb/Hybrid-Mount/daemon.log",
   BINARY: "/data/adb/modules/Hybrid-Mount/Hybrid-Mount",
 };
 
@@ -17,6 +17,75 @@ const localeModules = import.meta.glob("../locales/*.json", { eager: true });
 
 type LocaleDict = any;
 
+const isObject = (v: unknown): v is Record<string, unknown> =>
+  typeof v === "object" && v !== null && !Array.isArray(v);
+
+const sameValue = (a: unknown, b: unknown) =>
+  JSON.stringify(a) === JSON.stringify(b);
+
+// RFC 7386 merge patch turning `from` into `to`.
+function createMergePatch(
+  from: unknown,
+  to: unknown,
+): Record<string, unknown> | undefined {
+  if (!isObject(from) || !isObject(to)) return undefined;
+
+  const patch: Record<string, unknown> = {};
+  for (const key of Object.keys(from)) {
+    if (!(key in to)) patch[key] = null;
+  }
+  for (const [key, value] of Object.entries(to)) {
+    const previous = from[key];
+    if (sameValue(previous, value)) continue;
+    patch[key] =
+      isObject(previous) && isObject(value)
+        ? createMergePatch(previous, value)
+        : value;
+  }
+  return patch;
+}
+
+function applyMergePatch(target: unknown, patch: unknown): unknown {
+  if (!isObject(patch)) return patch;
+
+  const result: Record<string, unknown> = isObject(target)
+    ? { ...target }
+    : {};
+  for (const [key, value] of Object.entries(patch)) {
+    if (value === null) delete result[key];
+    else result[key] = applyMergePatch(result[key], value);
+  }
+  return result;
+}
+
+// Lists that accumulate across config.d layers. A changed list is rebased on
+// the entries config.toml itself holds, so drop-in entries are never written
+// back into it.
+const ACCUMULATED_LISTS = ["partitions", "overrides"];
+
+function rebaseLists(
+  patch: Record<string, unknown>,
+  effective: Record<string, unknown>,
+  file: Record<string, unknown>,
+): Record<string, unknown> {
+  const rebased = { ...patch };
+  for (const key of ACCUMULATED_LISTS) {
+    const next = patch[key];
+    const previous = effective[key];
+    if (!Array.isArray(next) || !Array.isArray(previous)) continue;
+
+    const own = Array.isArray(file[key]) ? (file[key] as unknown[]) : [];
+    const kept = own.filter((item) => next.some((n) => sameValue(n, item)));
+    const added = next.filter(
+      (item) =>
+        !previous.some((p) => sameValue(p, item)) &&
+        !kept.some((k) => sameValue(k, item)),
+    );
+    rebased[key] = [...kept, ...added];
+  }
+  return rebased;
+}
+
 export interface LogEntry {
   text: string;
   type: "info" | "warn" | "error" | "debug";
@@ -36,6 +105,14 @@ const createGlobalStore = () => {
   const [fixBottomNav, setFixBottomNavSignal] = createSignal(false);
 
   const [config, setConfig] = createSignal<AppConfig>(DEFAULT_CONFIG);
+  const [savedConfig, setSavedConfig] =
+    createSignal<AppConfig>(DEFAULT_CONFIG);
+  const [configRevision, setConfigRevision] = createSignal<string | null>(
+    null,
+  );
+  const [configFile, setConfigFile] = createSignal<Record<string, unknown>>(
+    {},
+  );
   const [modules, setModules] = createSignal<Module[]>([]);
   const [device, setDevice] = createSignal<DeviceInfo>({
     model: "-",
@@ -149,7 +226,10 @@ const createGlobalStore = () => {
     setLoadingConfig(true);
     try {
       const data = await API.loadConfig();
-      setConfig(data);
+      setConfig(data.config);
+      setSavedConfig(data.config);
+      setConfigRevision(data.revision);
+      setConfigFile(data.file);
     } catch (e) {
       showToast(L().config?.loadError || "Failed to load config", "error");
     }
@@ -159,7 +239,17 @@ const createGlobalStore = () => {
   async function saveConfig() {
     setSavingConfig(true);
     try {
-      await API.saveConfig(config());
+      const report = await API.validateConfig(config());
+      const error = report.issues.find((i) => i.severity === "error");
+      if (error) {
+        showToast(`${error.path}: ${error.message}`, "error");
+        setSavingConfig(false);
+        return;
+      }
+      await patchConfig(
+        createMergePatch(savedConfig(), config()) ?? {},
+        config(),
+      );
       showToast(L().common?.saved || "Saved", "success");
     } catch (e) {
       showToast(L().config?.saveFailed || "Failed to save config", "error");
@@ -167,6 +257,29 @@ const createGlobalStore = () => {
     setSavingConfig(false);
   }
 
+  async function patchConfig(patch: Record<string, unknown>, next?: AppConfig) {
+    if (Object.keys(patch).length === 0) return;
+    // The patch is computed against the effective config but written to
+    // config.toml only.
+    const filePatch = rebaseLists(
+      patch,
+      savedConfig() as unknown as Record<string, unknown>,
+      configFile(),
+    );
+    const result = await API.patchConfig(filePatch, configRevision());
+    setConfigRevision(result.revision);
+    setConfigFile(
+      applyMergePatch(configFile(), filePatch) as Record<string, unknown>,
+    );
+    setSavedConfig(next ?? ({ ...savedConfig(), ...patch } as AppConfig));
+    if (result.shadowed.length > 0) {
+      showToast(
+        `${L().config?.shadowed || "Overridden by config.d"}: ${result.shadowed.join(", ")}`,
+        "info",
+      );
+    }
+  }
+
   async function resetConfig() {
     setSavingConfig(true);
     try {
@@ -264,6 +377,7 @@ const createGlobalStore = () => {
     },
     loadConfig,
     saveConfig,
+    patchConfig,
     resetConfig,
 
     get modules() {
@@ -3,24 +3,64 @@
  * SPDX-License-Identifier: GPL-3.0-or-later
  */
 
+export interface RuleCondition {
+  sdk_min?: number;
+  sdk_max?: number;
+  device?: string[];
+  kernel_min?: string;
+  kernel_max?: string;
+  selinux_enforcing?: boolean;
+}
+
+export interface RuleOverride {
+  when: RuleCondition;
+  default_mode?: MountMode;
+  paths?: Record<string, MountMode>;
+}
+
 export interface ModuleRules {
   default_mode: MountMode;
   paths: Record<string, string>;
+  overrides?: RuleOverride[];
 }
 
-export type OverlayMode = "TMPFS" | "Ext4" | "Erofs";
+export type OverlayMode = "tmpfs" | "ext4" | "erofs";
+
+export type DefaultMode = "overlay" | "magic";
+
+export interface ConfigProfile {
+  moduledir?: string;
+  mountsource?: string;
+  partitions?: string[];
+  overlay_mode?: OverlayMode;
+  disable_umount?: boolean;
+  allow_umount_coexistence?: boolean;
+  default_mode?: DefaultMode;
+  partition_rules?: Record<string, MountMode>;
+  priority?: Record<string, number>;
+  rules?: Record<string, ModuleRules>;
+}
 
 export interface AppConfig {
+  config_version?: number;
   moduledir: string;
   mountsource: string;
   partitions: string[];
   overlay_mode: OverlayMode;
   disable_umount: boolean;
   allow_umount_coexistence: boolean;
-  logfile?: string;
+  default_mode?: DefaultMode;
+  rules?: Record<string, ModuleRules>;
+  partition_rules?: Record<string, MountMode>;
+  priority?: Record<string, number>;
+  conflict_resolution?: Record<string, string>;
+  sync?: { hash?: boolean; include?: string[]; exclude?: string[] };
+  active_profile?: string | null;
+  profiles?: Record<string, ConfigProfile>;
+  overrides?: (ConfigProfile & { when: RuleCondition })[];
 }
 
-export type MountMode = "Overlay" | "Magic" | "Ignore";
+export type MountMode = "overlay" | "magic" | "ignore";
 
 export interface Module {
   id: string;
@@ -28,13 +68,41 @@ export interface Module {
   version: string;
   author: string;
   description: string;
+  version_code?: number | null;
+  update_json?: string | null;
   mode: string;
   is_mounted: boolean;
+  exclusion?: string | null;
+  priority?: number;
+  layer?: number;
   enabled?: boolean;
   source_path?: string;
   rules: ModuleRules;
 }
 
+export interface ValidationIssue {
+  path: string;
+  severity: "error" | "warning";
+  message: string;
+}
+
+export interface ValidationReport {
+  valid: boolean;
+  issues: ValidationIssue[];
+}
+
+export interface ConfigSnapshot {
+  config: AppConfig;
+  revision: string | null;
+  // config.toml alone, without config.d drop-ins.
+  file: Record<string, unknown>;
+}
+
+export interface PatchResult {
+  revision: string;
+  shadowed: string[];
+}
+
 export interface StorageStatus {
   type: "tmpfs" | "ext4" | "erofs" | "unknown" | null;
   error?: string;
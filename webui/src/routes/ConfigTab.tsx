@@ -6,7 +6,6 @@
 import { createSignal, createEffect, createMemo, Show, For } from "solid-js";
 import { store } from "../lib/store";
 import { ICONS } from "../lib/constants";
-import { API } from "../lib/api";
 import ChipInput from "../components/ChipInput";
 import BottomActions from "../components/BottomActions";
 import "./ConfigTab.css";
@@ -108,7 +107,7 @@ export default function ConfigTab() {
 
     updateConfig(key, newVal);
 
-    API.saveConfig({ ...store.config, [key]: newVal }).catch(() => {
+    store.patchConfig({ [key]: newVal }).catch(() => {
       updateConfig(key, currentVal);
       store.showToast(
         store.L.config?.saveFailed || "Failed to update setting",
@@ -117,6 +116,25 @@ export default function ConfigTab() {
     });
   }
 
+  function setActiveProfile(name: string | null) {
+    const previous = store.config.active_profile ?? null;
+    if (previous === name) return;
+
+    updateConfig("active_profile", name);
+
+    store.patchConfig({ active_profile: name }).catch(() => {
+      updateConfig("active_profile", previous);
+      store.showToast(
+        store.L.config?.saveFailed || "Failed to update setting",
+        "error",
+      );
+    });
+  }
+
+  const profileNames = createMemo(() =>
+    Object.keys(store.config.profiles ?? {}).sort(),
+  );
+
   function setOverlayMode(mode: string) {
     updateConfig("overlay_mode", mode as OverlayMode);
   }
@@ -284,6 +302,55 @@ export default function ConfigTab() {
           </div>
         </section>
 
+        <Show when={profileNames().length > 0}>
+          <section class="config-group">
+            <div class="config-card">
+              <div class="card-header">
+                <div class="card-icon">
+                  <md-icon>
+                    <svg viewBox="0 0 24 24">
+                      <path d={ICONS.settings} />
+                    </svg>
+                  </md-icon>
+                </div>
+                <div class="card-text">
+                  <span class="card-title">
+                    {store.L.config?.profile || "Profile"}
+                  </span>
+                  <span class="card-desc">
+                    {store.L.config?.profileDesc ||
+                      "Overrides the settings below until switched off"}
+                  </span>
+                </div>
+              </div>
+              <div class="mode-selector">
+                <For each={[null, ...profileNames()]}>
+                  {(name) => (
+                    <button
+                      class={`mode-item ${(store.config.active_profile ?? null) === name ? "selected" : ""}`}
+                      onClick={() => setActiveProfile(name)}
+                    >
+                      <md-ripple></md-ripple>
+                      <div class="mode-info">
+                        <span class="mode-title">
+                          {name ?? (store.L.config?.profileNone || "None")}
+                        </span>
+                      </div>
+                      <div class="mode-check">
+                        <md-icon>
+                          <svg viewBox="0 0 24 24">
+                            <path d="M21,7L9,19L3.5,13.5L4.91,12.09L9,16.17L19.59,5.59L21,7Z" />
+                          </svg>
+                        </md-icon>
+                      </div>
+                    </button>
+                  )}
+                </For>
+              </div>
+            </div>
+          </section>
+        </Show>
+
         <section class="config-group">
           <div class="config-card">
             <div class="card-header">